@@ -0,0 +1,354 @@
+use clap::error::ErrorKind;
+use clap::{Command, Subcommand};
+use freeswitch_sys::*;
+use serde::Serialize;
+use std::error::Error;
+use std::ffi::CString;
+use std::fmt::Display;
+use std::io::Write;
+use std::{mem, ptr, slice};
+
+use crate::{core::Session, prelude::*};
+
+/// Initial size of the output buffer, as `SWITCH_CMD_CHUNK_LEN`.
+const CMD_CHUNK_LEN: usize = 1024;
+
+/// A stream collecting api output into a malloc'd buffer, as `SWITCH_STANDARD_STREAM`.
+struct StandardStream(switch_stream_handle_t);
+
+impl StandardStream {
+    fn new() -> Result<Self> {
+        // SAFETY: the handle is plain data, and FS reallocs the buffer as it is written to
+        unsafe {
+            let data = malloc(CMD_CHUNK_LEN as _);
+            if data.is_null() {
+                return Err(switch_status_t::SWITCH_STATUS_MEMERR.into());
+            }
+            ptr::write_bytes(data as *mut u8, 0, CMD_CHUNK_LEN);
+            let mut stream: switch_stream_handle_t = mem::zeroed();
+            stream.data = data;
+            stream.end = data;
+            stream.data_size = CMD_CHUNK_LEN as _;
+            stream.alloc_len = CMD_CHUNK_LEN as _;
+            stream.alloc_chunk = CMD_CHUNK_LEN as _;
+            stream.write_function = Some(switch_console_stream_write);
+            stream.raw_write_function = Some(switch_console_stream_raw_write);
+            Ok(Self(stream))
+        }
+    }
+
+    fn output(&self) -> String {
+        if self.0.data.is_null() {
+            return String::new();
+        }
+        // SAFETY: data_len bytes have been written to the buffer
+        let data = unsafe { slice::from_raw_parts(self.0.data as *const u8, self.0.data_len as _) };
+        String::from_utf8_lossy(data).into_owned()
+    }
+}
+
+impl Drop for StandardStream {
+    fn drop(&mut self) {
+        // SAFETY: the buffer came from malloc, or FS's realloc of it
+        unsafe { free(self.0.data) }
+    }
+}
+
+/// Run an api command and collect its output. See: [`switch_api_execute`](../../freeswitch_sys/fn.switch_api_execute.html).
+///
+/// Fails when the api is unknown or returns an error status, as rust apis do when returning an
+/// [`FSError`]. Most apis report failures in their output instead, ie `-ERR No such channel!`,
+/// so check the reply as well.
+///
+/// # Examples
+///
+/// ```
+/// let channels: serde_json::Value = serde_json::from_str(&api::execute("show", "channels as json", None)?)?;
+/// api::execute("uuid_kill", &format!("{uuid} NORMAL_CLEARING"), None)?;
+/// ```
+pub fn execute(cmd: &str, args: &str, session: Option<&Session>) -> Result<String> {
+    let to_cstring = |s: &str| CString::new(s).map_err(|_| switch_status_t::SWITCH_STATUS_GENERR);
+    let cmd = to_cstring(cmd)?;
+    let args = to_cstring(args)?;
+    let session = session.map_or(ptr::null_mut(), |s| s.as_ptr());
+    let mut stream = StandardStream::new()?;
+    // SAFETY: the stream outlives the call, and FS copies what it needs from the strings
+    match unsafe { switch_api_execute(cmd.as_ptr(), args.as_ptr(), session, &mut stream.0) } {
+        switch_status_t::SWITCH_STATUS_SUCCESS => Ok(stream.output()),
+        other => Err(other.into()),
+    }
+}
+
+/// Parse an api command line into a clap [`Subcommand`], ie `start <uuid> '{"a": "b"}'`.
+///
+/// The line is split into words as a shell would, so arguments containing spaces can be quoted.
+/// `help` returns the usage as an [`ErrorKind::DisplayHelp`] error, while an empty line is
+/// rejected along with the usage.
+///
+/// # Examples
+///
+/// ```
+/// #[derive(Subcommand)]
+/// enum Cmd {
+///     Stop { uuid: String },
+/// }
+///
+/// let Cmd::Stop { uuid } = parse_args::<Cmd>("myapi", "stop 'a b'")?;
+/// assert_eq!(uuid, "a b");
+/// ```
+pub fn parse_args<T: Subcommand>(
+    name: &'static str,
+    cmd: &str,
+) -> std::result::Result<T, clap::Error> {
+    let words = split_args(cmd).map_err(|e| clap::Error::raw(ErrorKind::InvalidValue, e))?;
+    let command = T::augment_subcommands(
+        Command::new(name)
+            .bin_name(name)
+            .no_binary_name(true)
+            .disable_version_flag(true)
+            .subcommand_required(true)
+            .arg_required_else_help(true),
+    );
+    let matches = command.try_get_matches_from(words)?;
+    T::from_arg_matches(&matches)
+}
+
+/// Parse the command for a handler declared with `#[switch_api_define(args = ..)]`,
+/// writing usage and parse errors to the stream instead of calling it.
+#[doc(hidden)]
+pub fn call_with_args<T, F>(
+    name: &'static str,
+    cmd: &str,
+    mut stream: StreamHandle,
+    handler: F,
+) -> switch_status_t
+where
+    T: Subcommand,
+    F: FnOnce(T, StreamHandle) -> switch_status_t,
+{
+    match parse_args(name, cmd) {
+        Ok(args) => handler(args, stream),
+        Err(e) => {
+            // Help is a normal response, anything else is a usage error
+            let _ = if e.use_stderr() {
+                write!(stream, "-ERR {}", e.render())
+            } else {
+                write!(stream, "{}", e.render())
+            };
+            switch_status_t::SWITCH_STATUS_SUCCESS
+        }
+    }
+}
+
+/// The value returned from an api handler, written to the caller's stream.
+///
+/// Handlers either return a `switch_status_t` and write their own output, or a `Result`
+/// which is rendered as `+OK <value>` or `-ERR <error>: <source>: ...`.
+/// An `-ERR` is returned to FS as a success, as callers like `mod_event_socket` replace
+/// the output of failed apis with a generic "Command not found". To fail with a status
+/// instead, return an [`FSError`], either directly or as the source of the error.
+#[diagnostic::on_unimplemented(
+    message = "api handlers return `switch_status_t` or `Result<impl Display, impl Error>`"
+)]
+pub trait ApiResponse {
+    fn write_response(self, stream: &mut StreamHandle) -> switch_status_t;
+}
+
+impl ApiResponse for switch_status_t {
+    fn write_response(self, _stream: &mut StreamHandle) -> switch_status_t {
+        self
+    }
+}
+
+impl<T, E> ApiResponse for std::result::Result<T, E>
+where
+    T: Display,
+    E: Into<Box<dyn Error + Send + Sync>>,
+{
+    fn write_response(self, stream: &mut StreamHandle) -> switch_status_t {
+        match self.map(|v| v.to_string()) {
+            Ok(v) => {
+                let _ = if v.is_empty() {
+                    writeln!(stream, "+OK")
+                } else {
+                    writeln!(stream, "+OK {v}")
+                };
+                switch_status_t::SWITCH_STATUS_SUCCESS
+            }
+            Err(e) => {
+                let e = e.into();
+                let _ = writeln!(stream, "-ERR {}", error_chain(&*e));
+                error_status(&*e)
+            }
+        }
+    }
+}
+
+/// The `Result` of an api handler declared with `format = "json"`, rendered as
+/// `{"status": "ok", "data": <value>}` or `{"status": "error", "message": "<error chain>"}`.
+/// Errors are returned to FS as with [`ApiResponse`].
+#[diagnostic::on_unimplemented(
+    message = "json formatted api handlers return `Result<impl Serialize, impl Error>`"
+)]
+pub trait JsonApiResponse {
+    fn write_json_response(self, stream: &mut StreamHandle) -> switch_status_t;
+}
+
+impl<T, E> JsonApiResponse for std::result::Result<T, E>
+where
+    T: Serialize,
+    E: Into<Box<dyn Error + Send + Sync>>,
+{
+    fn write_json_response(self, stream: &mut StreamHandle) -> switch_status_t {
+        let mut status = switch_status_t::SWITCH_STATUS_SUCCESS;
+        let reply = match self {
+            Ok(v) => serde_json::to_value(v)
+                .map(|data| serde_json::json!({ "status": "ok", "data": data }))
+                .map_err(|e| e.to_string()),
+            Err(e) => {
+                let e = e.into();
+                status = error_status(&*e);
+                Err(error_chain(&*e))
+            }
+        }
+        .unwrap_or_else(|message| serde_json::json!({ "status": "error", "message": message }));
+        let _ = writeln!(stream, "{reply}");
+        status
+    }
+}
+
+/// Tell the caller an api panicked, reported like any other failed command.
+#[doc(hidden)]
+pub fn write_panic_response(name: &str, stream: &mut StreamHandle) -> switch_status_t {
+    let _ = writeln!(stream, "-ERR {name} panicked");
+    switch_status_t::SWITCH_STATUS_SUCCESS
+}
+
+/// The status of the first [`FSError`] in the error's chain, otherwise success.
+fn error_status(e: &(dyn Error + 'static)) -> switch_status_t {
+    let mut source = Some(e);
+    while let Some(e) = source {
+        if let Some(e) = e.downcast_ref::<FSError>() {
+            return e.status();
+        }
+        source = e.source();
+    }
+    switch_status_t::SWITCH_STATUS_SUCCESS
+}
+
+/// Render an error with its sources, ie `failed to start: connection refused`.
+fn error_chain(e: &dyn Error) -> String {
+    let mut msg = e.to_string();
+    let mut source = e.source();
+    while let Some(s) = source {
+        msg.push_str(&format!(": {s}"));
+        source = s.source();
+    }
+    msg
+}
+
+/// Split a command line into words, honouring single quotes, double quotes and backslash escapes.
+fn split_args(cmd: &str) -> std::result::Result<Vec<String>, &'static str> {
+    let mut words = vec![];
+    let mut word = String::new();
+    // Tracked separately so quoted empty strings are kept as words
+    let mut in_word = false;
+    let mut chars = cmd.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated single quote"),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err("unterminated double quote"),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated double quote"),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                match chars.next() {
+                    Some(c) => word.push(c),
+                    None => return Err("trailing backslash"),
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_args_handles_quotes_and_escapes() {
+        assert_eq!(
+            split_args(r#"start  uuid '{"a": "b c"}' "say \"hi\"" '' a\ b"#).unwrap(),
+            ["start", "uuid", r#"{"a": "b c"}"#, r#"say "hi""#, "", "a b"]
+        );
+        assert!(split_args("start 'uuid").is_err());
+    }
+
+    #[test]
+    fn error_chain_includes_sources() {
+        #[derive(Debug)]
+        struct StartFailed(std::num::ParseIntError);
+        impl Display for StartFailed {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str("failed to start")
+            }
+        }
+        impl Error for StartFailed {
+            fn source(&self) -> Option<&(dyn Error + 'static)> {
+                Some(&self.0)
+            }
+        }
+
+        let e = StartFailed("port".parse::<u16>().unwrap_err());
+        assert_eq!(
+            error_chain(&e),
+            "failed to start: invalid digit found in string"
+        );
+    }
+
+    #[test]
+    fn error_status_finds_fs_errors() {
+        let e = FSError::from(switch_status_t::SWITCH_STATUS_NOTFOUND);
+        assert_eq!(error_status(&e), switch_status_t::SWITCH_STATUS_NOTFOUND);
+        let e = "port".parse::<u16>().unwrap_err();
+        assert_eq!(error_status(&e), switch_status_t::SWITCH_STATUS_SUCCESS);
+    }
+}
@@ -0,0 +1,300 @@
+use freeswitch_sys::*;
+use std::ffi::c_void;
+use std::ffi::CString;
+use std::ptr;
+use std::slice;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::prelude::*;
+
+use crate::Frame;
+
+/// A speech recogniser implemented in rust, registered via [`FSModuleInterface::add_asr_interface`].
+///
+/// A new instance is created per ASR handle, ie per `detect_speech` on a channel.
+/// FS feeds audio from the media thread while polling for results from another,
+/// so calls are serialised through a lock around the instance.
+pub trait SpeechRecognizer: Sized + Send + 'static {
+    const NAME: &'static str;
+
+    /// `codec` and `rate` describe the linear audio that will be fed, `dest` is an optional
+    /// engine specific destination.
+    fn open(codec: &str, rate: u32, dest: Option<&str>) -> Result<Self>;
+
+    fn load_grammar(&mut self, grammar: &str, name: &str) -> Result<()>;
+
+    fn unload_grammar(&mut self, _name: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// Feed a frame of signed 16bit linear audio to the recogniser.
+    fn feed(&mut self, audio: &Frame) -> Result<()>;
+
+    /// Return true once results are ready to be collected.
+    fn check_results(&mut self) -> bool;
+
+    /// Collect the results, normally as NLSML xml.
+    fn get_results(&mut self) -> Result<String>;
+
+    fn pause(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn resume(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn start_input_timers(&mut self) {}
+
+    fn text_param(&mut self, _param: &str, _val: &str) {}
+
+    fn numeric_param(&mut self, _param: &str, _val: i32) {}
+
+    fn float_param(&mut self, _param: &str, _val: f64) {}
+
+    fn close(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl FSModuleInterface {
+    /// Register a speech recogniser for use with `detect_speech` and `play_and_detect_speech`.
+    pub fn add_asr_interface<T: SpeechRecognizer>(&self) {
+        let t = switch_module_interface_name_t::SWITCH_ASR_INTERFACE;
+        // SAFETY: We assume the module ptr given to us is valid
+        // also we restrict access to the builder to ONLY the load function
+        unsafe {
+            let ptr = freeswitch_sys::switch_loadable_module_create_interface(*self.0, t)
+                as *mut switch_asr_interface_t;
+            let interface = &mut *ptr;
+            interface.interface_name = CString::new(T::NAME).unwrap().into_raw();
+            interface.asr_open = Some(asr_open_raw::<T>);
+            interface.asr_load_grammar = Some(asr_load_grammar_raw::<T>);
+            interface.asr_unload_grammar = Some(asr_unload_grammar_raw::<T>);
+            interface.asr_close = Some(asr_close_raw::<T>);
+            interface.asr_feed = Some(asr_feed_raw::<T>);
+            interface.asr_resume = Some(asr_resume_raw::<T>);
+            interface.asr_pause = Some(asr_pause_raw::<T>);
+            interface.asr_check_results = Some(asr_check_results_raw::<T>);
+            interface.asr_get_results = Some(asr_get_results_raw::<T>);
+            interface.asr_start_input_timers = Some(asr_start_input_timers_raw::<T>);
+            interface.asr_text_param = Some(asr_text_param_raw::<T>);
+            interface.asr_numeric_param = Some(asr_numeric_param_raw::<T>);
+            interface.asr_float_param = Some(asr_float_param_raw::<T>);
+        }
+    }
+}
+
+unsafe fn recognizer<'a, T>(ah: *mut switch_asr_handle_t) -> Option<MutexGuard<'a, T>> {
+    ah.as_ref()
+        .and_then(|h| (h.private_info as *const Mutex<T>).as_ref())
+        .map(|m| m.lock().unwrap_or_else(PoisonError::into_inner))
+}
+
+unsafe extern "C" fn asr_open_raw<T: SpeechRecognizer>(
+    ah: *mut switch_asr_handle_t,
+    codec: *const ::std::os::raw::c_char,
+    rate: ::std::os::raw::c_int,
+    dest: *const ::std::os::raw::c_char,
+    _flags: *mut switch_asr_flag_t,
+) -> switch_status_t {
+    catch_panic("asr_open", ptr::null_mut(), || {
+        if ah.is_null() {
+            return switch_status_t::SWITCH_STATUS_FALSE;
+        }
+        let codec = str_arg(codec).unwrap_or_default();
+        let dest = str_arg(dest);
+        match T::open(&codec, rate.max(0) as u32, dest.as_deref()) {
+            Ok(r) => {
+                (*ah).private_info = Box::into_raw(Box::new(Mutex::new(r))) as *mut c_void;
+                switch_status_t::SWITCH_STATUS_SUCCESS
+            }
+            Err(e) => e.into(),
+        }
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
+
+unsafe extern "C" fn asr_close_raw<T: SpeechRecognizer>(
+    ah: *mut switch_asr_handle_t,
+    _flags: *mut switch_asr_flag_t,
+) -> switch_status_t {
+    catch_panic("asr_close", ptr::null_mut(), || {
+        let Some(h) = ah.as_mut() else {
+            return switch_status_t::SWITCH_STATUS_FALSE;
+        };
+        let ptr = h.private_info as *mut Mutex<T>;
+        h.private_info = ptr::null_mut();
+        if ptr.is_null() {
+            return switch_status_t::SWITCH_STATUS_SUCCESS;
+        }
+        // take back ownership of box so we can clean up
+        let r = Box::from_raw(ptr)
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+        into_status(r.close())
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
+
+unsafe extern "C" fn asr_load_grammar_raw<T: SpeechRecognizer>(
+    ah: *mut switch_asr_handle_t,
+    grammar: *const ::std::os::raw::c_char,
+    name: *const ::std::os::raw::c_char,
+) -> switch_status_t {
+    catch_panic("asr_load_grammar", ptr::null_mut(), || {
+        let Some(mut r) = recognizer::<T>(ah) else {
+            return switch_status_t::SWITCH_STATUS_FALSE;
+        };
+        let grammar = str_arg(grammar).unwrap_or_default();
+        let name = str_arg(name).unwrap_or_default();
+        into_status(r.load_grammar(&grammar, &name))
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
+
+unsafe extern "C" fn asr_unload_grammar_raw<T: SpeechRecognizer>(
+    ah: *mut switch_asr_handle_t,
+    name: *const ::std::os::raw::c_char,
+) -> switch_status_t {
+    catch_panic("asr_unload_grammar", ptr::null_mut(), || {
+        let Some(mut r) = recognizer::<T>(ah) else {
+            return switch_status_t::SWITCH_STATUS_FALSE;
+        };
+        let name = str_arg(name).unwrap_or_default();
+        into_status(r.unload_grammar(&name))
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
+
+unsafe extern "C" fn asr_feed_raw<T: SpeechRecognizer>(
+    ah: *mut switch_asr_handle_t,
+    data: *mut c_void,
+    len: ::std::os::raw::c_uint,
+    _flags: *mut switch_asr_flag_t,
+) -> switch_status_t {
+    catch_panic("asr_feed", ptr::null_mut(), || {
+        let Some(mut r) = recognizer::<T>(ah) else {
+            return switch_status_t::SWITCH_STATUS_FALSE;
+        };
+        if data.is_null() {
+            return switch_status_t::SWITCH_STATUS_SUCCESS;
+        }
+        let mut frame = Frame::new(slice::from_raw_parts_mut(data as *mut u8, len as usize));
+        frame.set_datalen(len as usize);
+        frame.set_samples(len as usize / 2);
+        frame.set_rate((*ah).rate);
+        into_status(r.feed(&frame))
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
+
+unsafe extern "C" fn asr_pause_raw<T: SpeechRecognizer>(
+    ah: *mut switch_asr_handle_t,
+) -> switch_status_t {
+    catch_panic("asr_pause", ptr::null_mut(), || {
+        let Some(mut r) = recognizer::<T>(ah) else {
+            return switch_status_t::SWITCH_STATUS_FALSE;
+        };
+        into_status(r.pause())
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
+
+unsafe extern "C" fn asr_resume_raw<T: SpeechRecognizer>(
+    ah: *mut switch_asr_handle_t,
+) -> switch_status_t {
+    catch_panic("asr_resume", ptr::null_mut(), || {
+        let Some(mut r) = recognizer::<T>(ah) else {
+            return switch_status_t::SWITCH_STATUS_FALSE;
+        };
+        into_status(r.resume())
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
+
+unsafe extern "C" fn asr_check_results_raw<T: SpeechRecognizer>(
+    ah: *mut switch_asr_handle_t,
+    _flags: *mut switch_asr_flag_t,
+) -> switch_status_t {
+    catch_panic("asr_check_results", ptr::null_mut(), || {
+        match recognizer::<T>(ah) {
+            Some(mut r) if r.check_results() => switch_status_t::SWITCH_STATUS_SUCCESS,
+            _ => switch_status_t::SWITCH_STATUS_FALSE,
+        }
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
+
+unsafe extern "C" fn asr_get_results_raw<T: SpeechRecognizer>(
+    ah: *mut switch_asr_handle_t,
+    xmlstr: *mut *mut ::std::os::raw::c_char,
+    _flags: *mut switch_asr_flag_t,
+) -> switch_status_t {
+    catch_panic("asr_get_results", ptr::null_mut(), || {
+        let Some(mut r) = recognizer::<T>(ah) else {
+            return switch_status_t::SWITCH_STATUS_FALSE;
+        };
+        if xmlstr.is_null() {
+            return switch_status_t::SWITCH_STATUS_FALSE;
+        }
+        match r.get_results().map(CString::new) {
+            Ok(Ok(results)) => {
+                // Callers free the results with free(), so it must come from the C allocator
+                *xmlstr = strdup(results.as_ptr());
+                switch_status_t::SWITCH_STATUS_SUCCESS
+            }
+            Ok(Err(_)) => switch_status_t::SWITCH_STATUS_GENERR,
+            Err(e) => e.into(),
+        }
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
+
+unsafe extern "C" fn asr_start_input_timers_raw<T: SpeechRecognizer>(
+    ah: *mut switch_asr_handle_t,
+) -> switch_status_t {
+    catch_panic("asr_start_input_timers", ptr::null_mut(), || {
+        if let Some(mut r) = recognizer::<T>(ah) {
+            r.start_input_timers();
+        }
+        switch_status_t::SWITCH_STATUS_SUCCESS
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
+
+unsafe extern "C" fn asr_text_param_raw<T: SpeechRecognizer>(
+    ah: *mut switch_asr_handle_t,
+    param: *mut ::std::os::raw::c_char,
+    val: *const ::std::os::raw::c_char,
+) {
+    catch_panic("asr_text_param", ptr::null_mut(), || {
+        if let (Some(mut r), Some(param)) = (recognizer::<T>(ah), str_arg(param)) {
+            r.text_param(&param, &str_arg(val).unwrap_or_default());
+        }
+    });
+}
+
+unsafe extern "C" fn asr_numeric_param_raw<T: SpeechRecognizer>(
+    ah: *mut switch_asr_handle_t,
+    param: *mut ::std::os::raw::c_char,
+    val: ::std::os::raw::c_int,
+) {
+    catch_panic("asr_numeric_param", ptr::null_mut(), || {
+        if let (Some(mut r), Some(param)) = (recognizer::<T>(ah), str_arg(param)) {
+            r.numeric_param(&param, val);
+        }
+    });
+}
+
+unsafe extern "C" fn asr_float_param_raw<T: SpeechRecognizer>(
+    ah: *mut switch_asr_handle_t,
+    param: *mut ::std::os::raw::c_char,
+    val: f64,
+) {
+    catch_panic("asr_float_param", ptr::null_mut(), || {
+        if let (Some(mut r), Some(param)) = (recognizer::<T>(ah), str_arg(param)) {
+            r.float_param(&param, val);
+        }
+    });
+}
@@ -0,0 +1,46 @@
+use freeswitch_sys::*;
+use std::ffi::CStr;
+
+use crate::prelude::*;
+
+fs_session_owned_type!(CallerProfile, *mut switch_caller_profile_t);
+
+macro_rules! profile_fields {
+    ($($field:ident),*) => {
+        $(
+            #[doc = concat!("Returns the `", stringify!($field), "` field of the caller profile.")]
+            pub fn $field(&self) -> Option<&CStr> {
+                // SAFETY: profile strings are allocated from the session pool
+                // so live as long as the profile
+                unsafe {
+                    let ptr = (*self.0).$field;
+                    if ptr.is_null() {
+                        None
+                    } else {
+                        Some(CStr::from_ptr(ptr))
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl<'a> CallerProfile<'a> {
+    profile_fields!(
+        username,
+        dialplan,
+        caller_id_name,
+        caller_id_number,
+        callee_id_name,
+        callee_id_number,
+        network_addr,
+        ani,
+        aniii,
+        rdnis,
+        destination_number,
+        source,
+        chan_name,
+        uuid,
+        context
+    );
+}
@@ -1,6 +1,7 @@
 use freeswitch_sys::*;
 use std::ffi::c_void;
 use std::ffi::CStr;
+use std::ffi::CString;
 use std::ptr;
 
 use crate::prelude::*;
@@ -152,6 +153,23 @@ impl<'a> Channel<'a> {
         }
     }
 
+    /// Set the current state of the channel. See: [`switch_channel_perform_set_state`](../../freeswitch_sys/fn.switch_channel_perform_set_state.html).
+    #[track_caller]
+    pub fn set_state(&self, state: switch_channel_state_t) -> switch_channel_state_t {
+        let loc = std::panic::Location::caller();
+        let file = CString::new(loc.file()).unwrap();
+        // SAFETY: FS takes the channel state lock internally
+        unsafe {
+            switch_channel_perform_set_state(
+                self.as_ptr(),
+                file.as_ptr(),
+                ptr::null(),
+                loc.line() as i32,
+                state,
+            )
+        }
+    }
+
     /// Add a state handler table to a given channel. Returns the index number/priority of the table.
     ///
     /// See: [`switch_channel_add_state_handler`](../../freeswitch_sys/fn.switch_channel_add_state_handler.html).
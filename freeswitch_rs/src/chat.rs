@@ -0,0 +1,97 @@
+use freeswitch_sys::*;
+use std::ffi::CStr;
+use std::ffi::CString;
+use std::ptr;
+
+use crate::prelude::*;
+
+use crate::event::Event;
+
+/// A chat protocol implemented in rust, registered via [`FSModuleInterface::add_chat_interface`].
+///
+/// FS delivers messages addressed to the protocol as `MESSAGE` events, with the routing
+/// details in the `proto`, `from`, `to` and `subject` headers and the text as the body.
+///
+/// # Examples
+///
+/// ```
+/// struct Sms;
+///
+/// impl ChatInterface for Sms {
+///     const NAME: &'static str = "sms_gateway";
+///
+///     fn chat_send(message: &Event) -> Result<()> {
+///         let to = message.get_header(c"to");
+///         let body = message.get_body();
+///         // hand off to the gateway ...
+///         Ok(())
+///     }
+/// }
+/// ```
+pub trait ChatInterface {
+    const NAME: &'static str;
+    fn chat_send(message: &Event) -> Result<()>;
+}
+
+impl FSModuleInterface {
+    /// Register a chat protocol, messages sent to `NAME` are passed to [`ChatInterface::chat_send`].
+    pub fn add_chat_interface<T: ChatInterface>(&self) {
+        let t = switch_module_interface_name_t::SWITCH_CHAT_INTERFACE;
+        // SAFETY: We assume the module ptr given to us is valid
+        // also we restrict access to the builder to ONLY the load function
+        unsafe {
+            let ptr = freeswitch_sys::switch_loadable_module_create_interface(*self.0, t)
+                as *mut switch_chat_interface_t;
+            let interface = &mut *ptr;
+            interface.interface_name = CString::new(T::NAME).unwrap().into_raw();
+            interface.chat_send = Some(chat_send_raw::<T>);
+        }
+    }
+}
+
+unsafe extern "C" fn chat_send_raw<T: ChatInterface>(
+    message_event: *mut switch_event_t,
+) -> switch_status_t {
+    catch_panic("chat_send", ptr::null_mut(), || {
+        if message_event.is_null() {
+            return switch_status_t::SWITCH_STATUS_FALSE;
+        }
+        // The event remains owned by FS, we only lend it out
+        let event = Event(message_event);
+        into_status(T::chat_send(&event))
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
+
+/// Build a `MESSAGE` event suitable for [`send_chat`].
+pub fn new_chat_message(proto: &str, from: &str, to: &str, body: &str) -> Result<Event> {
+    let mut event = Event::new_core_event(switch_event_types_t::SWITCH_EVENT_MESSAGE, None)?;
+    event.add_header(c"proto", proto)?;
+    event.add_header(c"from", from)?;
+    event.add_header(c"to", to)?;
+    event.set_body(body)?;
+    Ok(event)
+}
+
+/// Send a message event to a chat protocol. See: [`switch_core_chat_send`](../../freeswitch_sys/fn.switch_core_chat_send.html).
+///
+/// Use `GLOBAL` as the destination to inject an inbound message, routing it through the chatplan.
+///
+/// # Examples
+///
+/// ```
+/// let mut message = new_chat_message("sms_gateway", "+15551234", "1000@default", "hello")?;
+/// message.add_header(c"subject", "SMS")?;
+/// send_chat(c"GLOBAL", message)?;
+/// ```
+pub fn send_chat(dest_proto: &CStr, mut message: Event) -> Result<()> {
+    // SAFETY: FS duplicates the event, so we're still responsible for the original
+    unsafe {
+        let res = switch_core_chat_send(dest_proto.as_ptr(), message.0);
+        switch_event_destroy(&mut message.0);
+        match res {
+            switch_status_t::SWITCH_STATUS_SUCCESS => Ok(()),
+            other => Err(other.into()),
+        }
+    }
+}
@@ -0,0 +1,344 @@
+use freeswitch_sys::*;
+use std::ffi::c_void;
+use std::ffi::CStr;
+use std::ffi::CString;
+use std::mem;
+use std::ptr;
+use std::slice;
+
+use crate::prelude::*;
+
+use crate::Frame;
+
+pub type CodecFlags = freeswitch_sys::switch_codec_flag_enum_t;
+
+/// A codec implemented in rust, registered via [`FSModuleInterface::add_codec`].
+///
+/// A new instance is created each time FS initialises the codec, ie per session leg
+/// and direction, and is dropped when FS destroys it.
+pub trait Codec: Sized + Send + 'static {
+    fn init(params: &CodecParams, flags: CodecFlags) -> Result<Self>;
+
+    /// Encode the linear audio in `decoded` into `encoded`.
+    /// Set the datalen (and rate if it differs from the implementation) of the encoded frame.
+    fn encode(&mut self, decoded: &Frame, encoded: &mut Frame) -> Result<()>;
+
+    /// Decode the audio in `encoded` into linear audio in `decoded`.
+    /// Set the datalen (and rate if it differs from the implementation) of the decoded frame.
+    fn decode(&mut self, encoded: &Frame, decoded: &mut Frame) -> Result<()>;
+
+    fn destroy(self) {}
+}
+
+/// Details of the implementation a codec instance was created with.
+#[derive(Debug, Clone)]
+pub struct CodecParams {
+    pub iananame: String,
+    pub rate: u32,
+    pub channels: u8,
+    pub samples_per_packet: u32,
+    pub decoded_bytes_per_packet: u32,
+    pub encoded_bytes_per_packet: u32,
+    pub fmtp: Option<String>,
+}
+
+/// Describes a single implementation of a codec, ie a combination of rate, ptime and channels.
+///
+/// # Examples
+///
+/// ```
+/// let implementation = CodecImplementation::new("L16")
+///     .ianacode(10)
+///     .rate(16000)
+///     .ptime(20)
+///     .channels(1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct CodecImplementation {
+    iananame: String,
+    ianacode: u8,
+    fmtp: Option<String>,
+    rate: u32,
+    ptime: u32,
+    channels: u8,
+    bits_per_second: Option<i32>,
+    encoded_bytes_per_packet: Option<u32>,
+}
+
+impl CodecImplementation {
+    /// Create a mono 8khz 20ms implementation, using a dynamic payload type.
+    pub fn new(iananame: &str) -> Self {
+        Self {
+            iananame: iananame.to_owned(),
+            ianacode: 96,
+            fmtp: None,
+            rate: 8000,
+            ptime: 20,
+            channels: 1,
+            bits_per_second: None,
+            encoded_bytes_per_packet: None,
+        }
+    }
+
+    pub fn ianacode(mut self, ianacode: u8) -> Self {
+        self.ianacode = ianacode;
+        self
+    }
+
+    pub fn fmtp(mut self, fmtp: &str) -> Self {
+        self.fmtp = Some(fmtp.to_owned());
+        self
+    }
+
+    /// Samples per second.
+    pub fn rate(mut self, rate: u32) -> Self {
+        self.rate = rate;
+        self
+    }
+
+    /// Packetisation time in milliseconds.
+    pub fn ptime(mut self, ptime: u32) -> Self {
+        self.ptime = ptime;
+        self
+    }
+
+    pub fn channels(mut self, channels: u8) -> Self {
+        self.channels = channels;
+        self
+    }
+
+    /// Defaults to the bitrate of the encoded packets.
+    pub fn bits_per_second(mut self, bps: i32) -> Self {
+        self.bits_per_second = Some(bps);
+        self
+    }
+
+    /// Defaults to the decoded size, ie an uncompressed codec.
+    pub fn encoded_bytes_per_packet(mut self, bytes: u32) -> Self {
+        self.encoded_bytes_per_packet = Some(bytes);
+        self
+    }
+
+    fn samples_per_packet(&self) -> u32 {
+        self.rate * self.ptime / 1000
+    }
+
+    fn decoded_bytes_per_packet(&self) -> u32 {
+        // FS linear audio is always 16bit
+        self.samples_per_packet() * 2 * self.channels as u32
+    }
+}
+
+impl FSModuleInterface {
+    /// Register a codec along with all of its implementations.
+    pub fn add_codec<T: Codec>(&self, name: &str, implementations: &[CodecImplementation]) {
+        let t = switch_module_interface_name_t::SWITCH_CODEC_INTERFACE;
+        // SAFETY: We assume the module ptr given to us is valid
+        // also we restrict access to the builder to ONLY the load function
+        unsafe {
+            let ptr = freeswitch_sys::switch_loadable_module_create_interface(*self.0, t)
+                as *mut switch_codec_interface_t;
+            let interface = &mut *ptr;
+            interface.interface_name = CString::new(name).unwrap().into_raw();
+            interface.modname = (**self.0).module_name as *mut _;
+            interface.codec_id = switch_core_codec_next_id();
+
+            // Mirrors switch_core_codec_add_implementation which is inline only
+            for i in implementations {
+                let decoded_bytes = i.decoded_bytes_per_packet();
+                if decoded_bytes > SWITCH_RECOMMENDED_BUFFER_SIZE {
+                    log::error!("Rejecting codec implementation {}, packets too large", name);
+                    continue;
+                }
+                let encoded_bytes = i.encoded_bytes_per_packet.unwrap_or(decoded_bytes);
+                let bits_per_second = i
+                    .bits_per_second
+                    .unwrap_or((encoded_bytes * 8 * 1000 / i.ptime.max(1)) as i32);
+
+                let mut imp: switch_codec_implementation_t = mem::zeroed();
+                imp.codec_type = switch_codec_type_t_SWITCH_CODEC_TYPE_AUDIO;
+                imp.ianacode = i.ianacode;
+                imp.iananame = CString::new(i.iananame.as_str()).unwrap().into_raw();
+                imp.fmtp = i
+                    .fmtp
+                    .as_ref()
+                    .map(|f| CString::new(f.as_str()).unwrap().into_raw())
+                    .unwrap_or(ptr::null_mut());
+                imp.samples_per_second = i.rate;
+                imp.actual_samples_per_second = i.rate;
+                imp.bits_per_second = bits_per_second;
+                imp.microseconds_per_packet = (i.ptime * 1000) as i32;
+                imp.samples_per_packet = i.samples_per_packet();
+                imp.decoded_bytes_per_packet = decoded_bytes;
+                imp.encoded_bytes_per_packet = encoded_bytes;
+                imp.number_of_channels = i.channels;
+                imp.codec_frames_per_packet = 1;
+                imp.init = Some(codec_init_raw::<T>);
+                imp.encode = Some(codec_encode_raw::<T>);
+                imp.decode = Some(codec_decode_raw::<T>);
+                imp.destroy = Some(codec_destroy_raw::<T>);
+                imp.codec_id = interface.codec_id;
+                imp.impl_id = switch_core_codec_next_id();
+                imp.modname = interface.modname;
+                imp.next = interface.implementations;
+                interface.implementations = Box::into_raw(Box::new(imp));
+            }
+        }
+    }
+}
+
+unsafe fn opt_string(ptr: *const ::std::os::raw::c_char) -> Option<String> {
+    if ptr.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(ptr).to_string_lossy().into_owned())
+    }
+}
+
+unsafe extern "C" fn codec_init_raw<T: Codec>(
+    codec: *mut switch_codec_t,
+    flags: switch_codec_flag_t,
+    _settings: *const switch_codec_settings_t,
+) -> switch_status_t {
+    catch_panic("codec_init", ptr::null_mut(), || {
+        let Some(imp) = codec.as_ref().and_then(|c| c.implementation.as_ref()) else {
+            return switch_status_t::SWITCH_STATUS_FALSE;
+        };
+        let params = CodecParams {
+            iananame: opt_string(imp.iananame).unwrap_or_default(),
+            rate: imp.samples_per_second,
+            channels: imp.number_of_channels,
+            samples_per_packet: imp.samples_per_packet,
+            decoded_bytes_per_packet: imp.decoded_bytes_per_packet,
+            encoded_bytes_per_packet: imp.encoded_bytes_per_packet,
+            fmtp: opt_string((*codec).fmtp_in),
+        };
+        match T::init(&params, CodecFlags(flags)) {
+            Ok(c) => {
+                (*codec).private_info = Box::into_raw(Box::new(c)) as *mut c_void;
+                switch_status_t::SWITCH_STATUS_SUCCESS
+            }
+            Err(e) => e.into(),
+        }
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
+
+// Encode and decode share the same shape, only the direction differs
+#[allow(clippy::too_many_arguments)]
+unsafe fn transcode<T: Codec>(
+    codec: *mut switch_codec_t,
+    input: *mut c_void,
+    input_len: u32,
+    input_rate: u32,
+    output: *mut c_void,
+    output_len: *mut u32,
+    output_rate: *mut u32,
+    f: impl FnOnce(&mut T, &Frame, &mut Frame) -> Result<()>,
+) -> switch_status_t {
+    let Some(state) = codec
+        .as_ref()
+        .and_then(|c| (c.private_info as *mut T).as_mut())
+    else {
+        return switch_status_t::SWITCH_STATUS_FALSE;
+    };
+    if input.is_null() || output.is_null() || output_len.is_null() {
+        return switch_status_t::SWITCH_STATUS_FALSE;
+    }
+
+    let mut input = Frame::new(slice::from_raw_parts_mut(
+        input as *mut u8,
+        input_len as usize,
+    ));
+    input.set_datalen(input_len as usize);
+    input.set_rate(input_rate);
+
+    // On entry the output len holds the size of the buffer
+    let mut output = Frame::new(slice::from_raw_parts_mut(
+        output as *mut u8,
+        *output_len as usize,
+    ));
+
+    match f(state, &input, &mut output) {
+        Ok(()) => {
+            *output_len = output.datalen() as u32;
+            if !output_rate.is_null() {
+                *output_rate = match output.rate() {
+                    0 => (*(*codec).implementation).samples_per_second,
+                    rate => rate,
+                };
+            }
+            switch_status_t::SWITCH_STATUS_SUCCESS
+        }
+        Err(e) => e.into(),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+unsafe extern "C" fn codec_encode_raw<T: Codec>(
+    codec: *mut switch_codec_t,
+    _other_codec: *mut switch_codec_t,
+    decoded_data: *mut c_void,
+    decoded_data_len: u32,
+    decoded_rate: u32,
+    encoded_data: *mut c_void,
+    encoded_data_len: *mut u32,
+    encoded_rate: *mut u32,
+    _flag: *mut ::std::os::raw::c_uint,
+) -> switch_status_t {
+    catch_panic("codec_encode", ptr::null_mut(), || {
+        transcode::<T>(
+            codec,
+            decoded_data,
+            decoded_data_len,
+            decoded_rate,
+            encoded_data,
+            encoded_data_len,
+            encoded_rate,
+            |c, input, output| c.encode(input, output),
+        )
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
+
+#[allow(clippy::too_many_arguments)]
+unsafe extern "C" fn codec_decode_raw<T: Codec>(
+    codec: *mut switch_codec_t,
+    _other_codec: *mut switch_codec_t,
+    encoded_data: *mut c_void,
+    encoded_data_len: u32,
+    encoded_rate: u32,
+    decoded_data: *mut c_void,
+    decoded_data_len: *mut u32,
+    decoded_rate: *mut u32,
+    _flag: *mut ::std::os::raw::c_uint,
+) -> switch_status_t {
+    catch_panic("codec_decode", ptr::null_mut(), || {
+        transcode::<T>(
+            codec,
+            encoded_data,
+            encoded_data_len,
+            encoded_rate,
+            decoded_data,
+            decoded_data_len,
+            decoded_rate,
+            |c, input, output| c.decode(input, output),
+        )
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
+
+unsafe extern "C" fn codec_destroy_raw<T: Codec>(codec: *mut switch_codec_t) -> switch_status_t {
+    catch_panic("codec_destroy", ptr::null_mut(), || {
+        if let Some(c) = codec.as_mut() {
+            let ptr = c.private_info as *mut T;
+            c.private_info = ptr::null_mut();
+            if !ptr.is_null() {
+                // take back ownership of box so we can clean up
+                Box::from_raw(ptr).destroy();
+            }
+        }
+        switch_status_t::SWITCH_STATUS_SUCCESS
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
@@ -0,0 +1,189 @@
+use freeswitch_sys::*;
+use std::ffi::CString;
+use std::ptr;
+
+use crate::prelude::*;
+
+use crate::core::{CallerProfile, Session};
+
+/// The applications a call should execute, returned from [`Dialplan::hunt`].
+///
+/// This is plain rust until handed back to FS, so routing logic can be unit tested without a
+/// running switch.
+///
+/// # Examples
+///
+/// ```
+/// let ext = CallerExtension::new("voicemail", "1000")
+///     .application("answer", "")
+///     .application("voicemail", "default $${domain} 1000");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CallerExtension {
+    name: String,
+    number: String,
+    applications: Vec<(String, String)>,
+}
+
+impl CallerExtension {
+    pub fn new(name: impl Into<String>, number: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            number: number.into(),
+            applications: vec![],
+        }
+    }
+
+    /// Append an application to execute, with its argument string.
+    pub fn application(mut self, app: impl Into<String>, data: impl Into<String>) -> Self {
+        self.applications.push((app.into(), data.into()));
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn number(&self) -> &str {
+        &self.number
+    }
+
+    /// The (application, argument) pairs in execution order.
+    pub fn applications(&self) -> &[(String, String)] {
+        &self.applications
+    }
+
+    /// Build the FS extension from the session pool. See: [`switch_caller_extension_new`](../../freeswitch_sys/fn.switch_caller_extension_new.html).
+    ///
+    /// # Safety
+    ///
+    /// `session` must be a valid session ptr
+    unsafe fn into_raw(
+        self,
+        session: *mut switch_core_session_t,
+    ) -> Result<*mut switch_caller_extension_t> {
+        let to_cstring =
+            |s: String| CString::new(s).map_err(|_| switch_status_t::SWITCH_STATUS_GENERR);
+        let name = to_cstring(self.name)?;
+        let number = to_cstring(self.number)?;
+        let applications = self
+            .applications
+            .into_iter()
+            .map(|(app, data)| Ok((to_cstring(app)?, to_cstring(data)?)))
+            .collect::<Result<Vec<_>>>()?;
+
+        // FS copies the strings into the session pool
+        let ext = switch_caller_extension_new(session, name.as_ptr(), number.as_ptr());
+        if ext.is_null() {
+            return Err(switch_status_t::SWITCH_STATUS_MEMERR.into());
+        }
+        for (app, data) in applications {
+            switch_caller_extension_add_application(session, ext, app.as_ptr(), data.as_ptr());
+        }
+        Ok(ext)
+    }
+}
+
+/// A dialplan implemented in rust, registered via [`FSModuleInterface::add_dialplan`].
+///
+/// Selected by a channel's dialplan, ie `<action application="transfer" data="1000 rust_dp"/>`,
+/// and hunted at routing time. Returning `None` hangs the call up with `NO_ROUTE_DESTINATION`.
+///
+/// # Examples
+///
+/// ```
+/// struct Routes;
+///
+/// impl Dialplan for Routes {
+///     const NAME: &'static str = "rust_dp";
+///
+///     fn hunt(_session: &Session, profile: &CallerProfile, _arg: Option<&str>) -> Option<CallerExtension> {
+///         match profile.destination_number()?.to_bytes() {
+///             b"9196" => Some(CallerExtension::new("echo", "9196").application("echo", "")),
+///             _ => None,
+///         }
+///     }
+/// }
+/// ```
+pub trait Dialplan {
+    const NAME: &'static str;
+
+    /// `arg` is the dialplan argument, ie the part after the colon in `rust_dp:arg`.
+    fn hunt(
+        session: &Session,
+        profile: &CallerProfile,
+        arg: Option<&str>,
+    ) -> Option<CallerExtension>;
+}
+
+impl FSModuleInterface {
+    /// Register a dialplan, selectable by `NAME` in a channel's dialplan string.
+    pub fn add_dialplan<T: Dialplan>(&self) {
+        let t = switch_module_interface_name_t::SWITCH_DIALPLAN_INTERFACE;
+        // SAFETY: We assume the module ptr given to us is valid
+        // also we restrict access to the builder to ONLY the load function
+        unsafe {
+            let ptr = freeswitch_sys::switch_loadable_module_create_interface(*self.0, t)
+                as *mut switch_dialplan_interface_t;
+            let interface = &mut *ptr;
+            interface.interface_name = CString::new(T::NAME).unwrap().into_raw();
+            interface.hunt_function = Some(hunt_raw::<T>);
+        }
+    }
+}
+
+unsafe extern "C" fn hunt_raw<T: Dialplan>(
+    session: *mut switch_core_session_t,
+    arg: *mut ::std::os::raw::c_void,
+    caller_profile: *mut switch_caller_profile_t,
+) -> *mut switch_caller_extension_t {
+    catch_panic("hunt", session, || {
+        if session.is_null() {
+            return ptr::null_mut();
+        }
+        // The core passes no profile when routing, so fallback to the channel's
+        let caller_profile = if caller_profile.is_null() {
+            let channel = switch_core_session_get_channel(session);
+            if channel.is_null() {
+                return ptr::null_mut();
+            }
+            switch_channel_get_caller_profile(channel)
+        } else {
+            caller_profile
+        };
+        if caller_profile.is_null() {
+            return ptr::null_mut();
+        }
+
+        let s = Session::from_ptr(session);
+        let profile = CallerProfile::from_ptr(caller_profile);
+        let arg = str_arg(arg as *const ::std::os::raw::c_char);
+        match T::hunt(&s, &profile, arg.as_deref()) {
+            Some(ext) => ext.into_raw(session).unwrap_or(ptr::null_mut()),
+            None => ptr::null_mut(),
+        }
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caller_extension_keeps_application_order() {
+        let ext = CallerExtension::new("park", "5900")
+            .application("answer", "")
+            .application("park", "");
+
+        assert_eq!(ext.name(), "park");
+        assert_eq!(ext.number(), "5900");
+        assert_eq!(
+            ext.applications(),
+            [
+                ("answer".to_string(), String::new()),
+                ("park".to_string(), String::new())
+            ]
+        );
+    }
+}
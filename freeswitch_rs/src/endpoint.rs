@@ -0,0 +1,465 @@
+use freeswitch_sys::*;
+use std::any::TypeId;
+use std::cell::UnsafeCell;
+use std::ffi::c_void;
+use std::ffi::CString;
+use std::mem;
+use std::ptr;
+use std::sync::Mutex;
+
+use crate::prelude::*;
+
+use crate::channel::DEFAULT_STATE_HANDLER_TABLE;
+use crate::codec::CodecFlags;
+use crate::core::{CallerProfile, Session, SessionExt, SessionMessage};
+use crate::event::Event;
+use crate::Frame;
+
+pub type CallCause = switch_call_cause_t;
+pub type Signal = switch_signal_t;
+
+/// An endpoint implemented in rust, registered via [`FSModuleInterface::add_endpoint`].
+///
+/// A new instance is created for every outgoing channel and lives until the session is destroyed.
+/// FS may read and write frames from different threads, hence methods take a shared reference.
+///
+/// Sessions are given L16 read and write codecs at `RATE` and `PTIME`, so frames carry
+/// signed 16 bit mono samples.
+///
+/// # Examples
+///
+/// ```
+/// struct Echo(Mutex<Vec<u8>>);
+///
+/// impl Endpoint for Echo {
+///     const NAME: &'static str = "echo";
+///
+///     fn outgoing_channel(
+///         _session: &Session,
+///         _parent: Option<&Session>,
+///         _profile: &CallerProfile,
+///         _vars: Option<&Event>,
+///     ) -> std::result::Result<Self, CallCause> {
+///         Ok(Echo(Mutex::new(vec![])))
+///     }
+///
+///     fn read_frame(&self, _session: &Session, frame: &mut Frame) -> Result<()> {
+///         let last = self.0.lock().unwrap();
+///         let len = last.len().min(frame.data_mut().len());
+///         frame.data_mut()[..len].copy_from_slice(&last[..len]);
+///         frame.set_datalen(len);
+///         frame.set_samples(len / 2);
+///         Ok(())
+///     }
+///
+///     fn write_frame(&self, _session: &Session, frame: &Frame) -> Result<()> {
+///         *self.0.lock().unwrap() = frame.data().to_vec();
+///         Ok(())
+///     }
+/// }
+/// ```
+pub trait Endpoint: Sized + Send + Sync + 'static {
+    const NAME: &'static str;
+    /// Sample rate of the session's codecs.
+    const RATE: u32 = 8000;
+    /// Milliseconds of audio in each frame.
+    const PTIME: u32 = 20;
+
+    /// Create the private state for a new outgoing channel.
+    ///
+    /// `session` is the newly requested session, with the caller profile already cloned onto its
+    /// channel. On error the session is destroyed and the cause returned to the originator.
+    fn outgoing_channel(
+        session: &Session,
+        parent: Option<&Session>,
+        profile: &CallerProfile,
+        vars: Option<&Event>,
+    ) -> std::result::Result<Self, CallCause>;
+
+    /// Fill the frame with the next chunk of media for the session.
+    /// Set the frame's datalen and samples to the amount written.
+    fn read_frame(&self, session: &Session, frame: &mut Frame) -> Result<()>;
+
+    /// Consume a frame of media written to the session.
+    fn write_frame(&self, session: &Session, frame: &Frame) -> Result<()>;
+
+    fn kill_channel(&self, _session: &Session, _sig: Signal) -> Result<()> {
+        Ok(())
+    }
+
+    fn receive_message(&self, _session: &Session, _msg: &SessionMessage) -> Result<()> {
+        Ok(())
+    }
+
+    fn on_init(&self, _session: &Session) -> Result<()> {
+        Ok(())
+    }
+
+    fn on_routing(&self, _session: &Session) -> Result<()> {
+        Ok(())
+    }
+
+    fn on_execute(&self, _session: &Session) -> Result<()> {
+        Ok(())
+    }
+
+    fn on_hangup(&self, _session: &Session) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called once the session is being destroyed, dropping the endpoint state afterwards.
+    fn on_destroy(self, _session: &Session) {}
+}
+
+// Outgoing channels need the interface ptr to request new sessions,
+// but FS doesn't hand it back to the io routines
+struct InterfacePtr(*mut switch_endpoint_interface_t);
+unsafe impl Send for InterfacePtr {}
+
+static INTERFACES: Mutex<Vec<(TypeId, InterfacePtr)>> = Mutex::new(Vec::new());
+
+fn interface_ptr<T: Endpoint>() -> Option<*mut switch_endpoint_interface_t> {
+    let interfaces = INTERFACES.lock().ok()?;
+    interfaces
+        .iter()
+        .find(|(id, _)| *id == TypeId::of::<T>())
+        .map(|(_, ptr)| ptr.0)
+}
+
+/// A linear codec owned by an endpoint session, destroyed when dropped.
+struct SessionCodec(Box<switch_codec_t>);
+
+impl SessionCodec {
+    /// Initialise an L16 codec from the session pool. See: [`switch_core_codec_init_with_bitrate`](../../freeswitch_sys/fn.switch_core_codec_init_with_bitrate.html).
+    unsafe fn new<T: Endpoint>(pool: *mut switch_memory_pool_t) -> Result<Self> {
+        let mut codec: Box<switch_codec_t> = Box::new(mem::zeroed());
+        let flags = CodecFlags::SWITCH_CODEC_FLAG_ENCODE | CodecFlags::SWITCH_CODEC_FLAG_DECODE;
+        match switch_core_codec_init_with_bitrate(
+            &mut *codec,
+            c"L16".as_ptr(),
+            ptr::null(),
+            ptr::null(),
+            T::RATE,
+            T::PTIME as _,
+            1,
+            0,
+            flags.0,
+            ptr::null(),
+            pool,
+        ) {
+            switch_status_t::SWITCH_STATUS_SUCCESS => Ok(Self(codec)),
+            other => Err(other.into()),
+        }
+    }
+}
+
+impl Drop for SessionCodec {
+    fn drop(&mut self) {
+        // SAFETY: the codec was initialised, and is dropped before the session pool is destroyed
+        unsafe { switch_core_codec_destroy(&mut *self.0) };
+    }
+}
+
+struct EndpointPrivate<T> {
+    endpoint: T,
+    // The session only keeps pointers to these, so they live as long as the endpoint
+    _read_codec: SessionCodec,
+    _write_codec: SessionCodec,
+    // Only touched by the read io routine, which FS calls from the session thread
+    // the frame handed back must outlive the call, so we keep it here
+    read_frame: UnsafeCell<switch_frame_t>,
+    read_buf: UnsafeCell<Box<[u8]>>,
+}
+
+impl<T: Endpoint> EndpointPrivate<T> {
+    unsafe fn get<'a>(session: *mut switch_core_session_t) -> Option<&'a Self> {
+        let ptr =
+            switch_core_session_get_private_class(session, switch_pvt_class_t_SWITCH_PVT_PRIMARY)
+                as *const Self;
+        ptr.as_ref()
+    }
+}
+
+impl FSModuleInterface {
+    /// Register an endpoint, making it available to dial strings as `NAME/<destination>`.
+    pub fn add_endpoint<T: Endpoint>(&self) {
+        let t = switch_module_interface_name_t::SWITCH_ENDPOINT_INTERFACE;
+        // SAFETY: We assume the module ptr given to us is valid
+        // also we restrict access to the builder to ONLY the load function
+        unsafe {
+            let ptr = freeswitch_sys::switch_loadable_module_create_interface(*self.0, t)
+                as *mut switch_endpoint_interface_t;
+            let interface = &mut *ptr;
+            interface.interface_name = CString::new(T::NAME).unwrap().into_raw();
+
+            // Tables must live as long as the module, so we leak them like the name
+            let mut io: switch_io_routines_t = mem::zeroed();
+            io.outgoing_channel = Some(outgoing_channel_raw::<T>);
+            io.read_frame = Some(read_frame_raw::<T>);
+            io.write_frame = Some(write_frame_raw::<T>);
+            io.kill_channel = Some(kill_channel_raw::<T>);
+            io.receive_message = Some(receive_message_raw::<T>);
+            interface.io_routines = Box::into_raw(Box::new(io));
+
+            let state_handler = switch_state_handler_table_t {
+                on_init: Some(on_init_raw::<T>),
+                on_routing: Some(on_routing_raw::<T>),
+                on_execute: Some(on_execute_raw::<T>),
+                on_hangup: Some(on_hangup_raw::<T>),
+                on_destroy: Some(on_destroy_raw::<T>),
+                ..DEFAULT_STATE_HANDLER_TABLE
+            };
+            interface.state_handler = Box::into_raw(Box::new(state_handler));
+
+            if let Ok(mut interfaces) = INTERFACES.lock() {
+                interfaces.retain(|(id, _)| *id != TypeId::of::<T>());
+                interfaces.push((TypeId::of::<T>(), InterfacePtr(ptr)));
+            }
+        }
+    }
+}
+
+#[track_caller]
+unsafe fn destroy_session(mut session: *mut switch_core_session_t) {
+    call_with_meta_suffix!(switch_core_session_perform_destroy, &mut session);
+}
+
+unsafe extern "C" fn outgoing_channel_raw<T: Endpoint>(
+    session: *mut switch_core_session_t,
+    var_event: *mut switch_event_t,
+    outbound_profile: *mut switch_caller_profile_t,
+    new_session: *mut *mut switch_core_session_t,
+    pool: *mut *mut switch_memory_pool_t,
+    flags: switch_originate_flag_t,
+    _cancel_cause: *mut switch_call_cause_t,
+) -> switch_call_cause_t {
+    catch_panic("outgoing_channel", session, || {
+        let Some(interface) = interface_ptr::<T>() else {
+            return CallCause::SWITCH_CAUSE_DESTINATION_OUT_OF_ORDER;
+        };
+        if outbound_profile.is_null() {
+            return CallCause::SWITCH_CAUSE_MANDATORY_IE_MISSING;
+        }
+
+        let ptr = switch_core_session_request_uuid(
+            interface,
+            switch_call_direction_t_SWITCH_CALL_DIRECTION_OUTBOUND,
+            flags,
+            pool,
+            ptr::null(),
+        );
+        if ptr.is_null() {
+            return CallCause::SWITCH_CAUSE_DESTINATION_OUT_OF_ORDER;
+        }
+        let s = Session::from_ptr(ptr);
+        let Some(channel) = s.get_channel() else {
+            destroy_session(ptr);
+            return CallCause::SWITCH_CAUSE_DESTINATION_OUT_OF_ORDER;
+        };
+
+        // profile is copied into the new session's pool
+        let profile = CallerProfile::from_ptr(switch_caller_profile_clone(ptr, outbound_profile));
+        switch_channel_set_caller_profile(channel.as_ptr(), profile.as_ptr());
+        let name = format!(
+            "{}/{}",
+            T::NAME,
+            profile
+                .destination_number()
+                .map(|d| d.to_string_lossy())
+                .unwrap_or_default()
+        );
+        if let Ok(name) = CString::new(name) {
+            switch_channel_set_name(channel.as_ptr(), name.as_ptr());
+        }
+
+        // FS won't read or write frames on a session without codecs
+        let pool = switch_core_session_get_pool(ptr);
+        let codecs =
+            SessionCodec::new::<T>(pool).and_then(|read| Ok((read, SessionCodec::new::<T>(pool)?)));
+        let Ok((mut read_codec, mut write_codec)) = codecs else {
+            destroy_session(ptr);
+            return CallCause::SWITCH_CAUSE_INCOMPATIBLE_DESTINATION;
+        };
+        switch_core_session_set_read_codec(ptr, &mut *read_codec.0);
+        switch_core_session_set_write_codec(ptr, &mut *write_codec.0);
+
+        let parent = (!session.is_null()).then(|| Session::from_ptr(session));
+        let vars = (!var_event.is_null()).then_some(Event(var_event));
+        // Caught here as well, so the new session isn't leaked when the endpoint panics
+        let res = catch_panic("outgoing_channel", session, || {
+            T::outgoing_channel(&s, parent.as_ref(), &profile, vars.as_ref())
+        });
+        let endpoint = match res {
+            Some(Ok(endpoint)) => endpoint,
+            Some(Err(cause)) => {
+                drop((read_codec, write_codec));
+                destroy_session(ptr);
+                return cause;
+            }
+            None => {
+                drop((read_codec, write_codec));
+                destroy_session(ptr);
+                return CallCause::SWITCH_CAUSE_DESTINATION_OUT_OF_ORDER;
+            }
+        };
+
+        let mut buf = vec![0u8; SWITCH_RECOMMENDED_BUFFER_SIZE as usize].into_boxed_slice();
+        let read_frame = Frame::new(&mut buf).0;
+        let data = Box::into_raw(Box::new(EndpointPrivate {
+            endpoint,
+            _read_codec: read_codec,
+            _write_codec: write_codec,
+            read_frame: UnsafeCell::new(read_frame),
+            read_buf: UnsafeCell::new(buf),
+        }));
+        switch_core_session_set_private_class(
+            ptr,
+            data as *mut c_void,
+            switch_pvt_class_t_SWITCH_PVT_PRIMARY,
+        );
+
+        channel.set_state(switch_channel_state_t::CS_INIT);
+        *new_session = ptr;
+        CallCause::SWITCH_CAUSE_SUCCESS
+    })
+    .unwrap_or(CallCause::SWITCH_CAUSE_DESTINATION_OUT_OF_ORDER)
+}
+
+unsafe extern "C" fn read_frame_raw<T: Endpoint>(
+    session: *mut switch_core_session_t,
+    frame: *mut *mut switch_frame_t,
+    _flags: switch_io_flag_t,
+    _stream_id: ::std::os::raw::c_int,
+) -> switch_status_t {
+    catch_panic("read_frame", session, || {
+        let Some(data) = EndpointPrivate::<T>::get(session) else {
+            return switch_status_t::SWITCH_STATUS_FALSE;
+        };
+        let s = Session::from_ptr(session);
+
+        // SAFETY: only the session thread reads frames, so we have exclusive access
+        let buf = &mut *data.read_buf.get();
+        let mut f = Frame::new(buf);
+        f.0.codec = switch_core_session_get_read_codec(session);
+        match data.endpoint.read_frame(&s, &mut f) {
+            Ok(()) => {
+                let read_frame = data.read_frame.get();
+                *read_frame = f.0;
+                *frame = read_frame;
+                switch_status_t::SWITCH_STATUS_SUCCESS
+            }
+            Err(e) => e.into(),
+        }
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
+
+unsafe extern "C" fn write_frame_raw<T: Endpoint>(
+    session: *mut switch_core_session_t,
+    frame: *mut switch_frame_t,
+    _flags: switch_io_flag_t,
+    _stream_id: ::std::os::raw::c_int,
+) -> switch_status_t {
+    catch_panic("write_frame", session, || {
+        let Some(data) = EndpointPrivate::<T>::get(session) else {
+            return switch_status_t::SWITCH_STATUS_FALSE;
+        };
+        if frame.is_null() {
+            return switch_status_t::SWITCH_STATUS_FALSE;
+        }
+        let s = Session::from_ptr(session);
+        let f = Frame::from_raw(frame);
+        match data.endpoint.write_frame(&s, &f) {
+            Ok(()) => switch_status_t::SWITCH_STATUS_SUCCESS,
+            Err(e) => e.into(),
+        }
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
+
+unsafe extern "C" fn kill_channel_raw<T: Endpoint>(
+    session: *mut switch_core_session_t,
+    sig: ::std::os::raw::c_int,
+) -> switch_status_t {
+    catch_panic("kill_channel", session, || {
+        let Some(data) = EndpointPrivate::<T>::get(session) else {
+            return switch_status_t::SWITCH_STATUS_SUCCESS;
+        };
+        let s = Session::from_ptr(session);
+        match data.endpoint.kill_channel(&s, switch_signal_t(sig as _)) {
+            Ok(()) => switch_status_t::SWITCH_STATUS_SUCCESS,
+            Err(e) => e.into(),
+        }
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
+
+unsafe extern "C" fn receive_message_raw<T: Endpoint>(
+    session: *mut switch_core_session_t,
+    msg: *mut switch_core_session_message_t,
+) -> switch_status_t {
+    catch_panic("receive_message", session, || {
+        let Some(data) = EndpointPrivate::<T>::get(session) else {
+            return switch_status_t::SWITCH_STATUS_SUCCESS;
+        };
+        if msg.is_null() {
+            return switch_status_t::SWITCH_STATUS_SUCCESS;
+        }
+        let s = Session::from_ptr(session);
+        let msg = SessionMessage::from_ptr(msg);
+        match data.endpoint.receive_message(&s, &msg) {
+            Ok(()) => switch_status_t::SWITCH_STATUS_SUCCESS,
+            Err(e) => e.into(),
+        }
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
+
+macro_rules! state_handler {
+    ($raw:ident, $method:ident) => {
+        unsafe extern "C" fn $raw<T: Endpoint>(
+            session: *mut switch_core_session_t,
+        ) -> switch_status_t {
+            catch_panic(stringify!($method), session, || {
+                let Some(data) = EndpointPrivate::<T>::get(session) else {
+                    return switch_status_t::SWITCH_STATUS_SUCCESS;
+                };
+                let s = Session::from_ptr(session);
+                match data.endpoint.$method(&s) {
+                    Ok(()) => switch_status_t::SWITCH_STATUS_SUCCESS,
+                    Err(e) => e.into(),
+                }
+            })
+            .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+        }
+    };
+}
+
+state_handler!(on_init_raw, on_init);
+state_handler!(on_routing_raw, on_routing);
+state_handler!(on_execute_raw, on_execute);
+state_handler!(on_hangup_raw, on_hangup);
+
+unsafe extern "C" fn on_destroy_raw<T: Endpoint>(
+    session: *mut switch_core_session_t,
+) -> switch_status_t {
+    catch_panic("on_destroy", session, || {
+        let ptr =
+            switch_core_session_get_private_class(session, switch_pvt_class_t_SWITCH_PVT_PRIMARY)
+                as *mut EndpointPrivate<T>;
+        if ptr.is_null() {
+            return switch_status_t::SWITCH_STATUS_SUCCESS;
+        }
+        switch_core_session_set_private_class(
+            session,
+            ptr::null_mut(),
+            switch_pvt_class_t_SWITCH_PVT_PRIMARY,
+        );
+        // take back ownership of box so we can clean up
+        let data = Box::from_raw(ptr);
+        let s = Session::from_ptr(session);
+        data.endpoint.on_destroy(&s);
+        switch_status_t::SWITCH_STATUS_SUCCESS
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
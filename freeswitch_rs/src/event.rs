@@ -2,7 +2,9 @@ use crate::utils::FSNewType;
 use freeswitch_sys::*;
 use std::{
     ffi::{CStr, CString},
+    marker::PhantomData,
     mem::MaybeUninit,
+    ops::Deref,
 };
 
 use crate::prelude::*;
@@ -11,7 +13,7 @@ use crate::channel::Channel;
 
 /// Wrapper around FreeSWITCH event.
 #[repr(transparent)]
-pub struct Event(*mut switch_event_t);
+pub struct Event(pub(crate) *mut switch_event_t);
 
 impl Event {
     /// Reserve a subclass name for private use with a custom event. See: [`switch_event_reserve_subclass_detailed`](../../freeswitch_sys/fn.switch_event_reserve_subclass_detailed.html).
@@ -38,6 +40,26 @@ impl Event {
         }
     }
 
+    /// Reserve several subclass names, freeing those already reserved if any fail.
+    /// The failing name is logged, as FS only reports that the module failed to load.
+    pub fn reserve_subclasses(names: &[&CStr]) -> Result<()> {
+        for (i, name) in names.iter().enumerate() {
+            if let Err(e) = Event::reserve_subclass(name) {
+                log::error!("Failed to reserve event subclass {:?}: {}", name, e);
+                Event::free_subclasses(&names[..i]);
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Free several subclass names, ignoring any that weren't reserved.
+    pub fn free_subclasses(names: &[&CStr]) {
+        for name in names {
+            let _ = Event::free_subclass(name);
+        }
+    }
+
     /// Create a new custom event with the given subclass name.
     pub fn new_custom_event(subclass: &CStr) -> Result<Self> {
         Event::new_core_event(switch_event_types_t::SWITCH_EVENT_CUSTOM, Some(subclass))
@@ -83,6 +105,50 @@ impl Event {
         }
     }
 
+    /// Add a header to an event, the value is copied. See: [`switch_event_add_header_string`](../../freeswitch_sys/fn.switch_event_add_header_string.html).
+    pub fn add_header<T>(&mut self, name: &CStr, value: T) -> Result<()>
+    where
+        T: Into<Vec<u8>>,
+    {
+        let cstr = CString::new(value).map_err(|_e| switch_status_t::SWITCH_STATUS_GENERR)?;
+        unsafe {
+            let res = switch_event_add_header_string(
+                self.0,
+                switch_stack_t_SWITCH_STACK_BOTTOM,
+                name.as_ptr(),
+                cstr.as_ptr(),
+            );
+            match res {
+                switch_status_t::SWITCH_STATUS_SUCCESS => Ok(()),
+                other => Err(other.into()),
+            }
+        }
+    }
+
+    /// Retrieve a header value from an event. See: [`switch_event_get_header_idx`](../../freeswitch_sys/fn.switch_event_get_header_idx.html).
+    pub fn get_header(&self, name: &CStr) -> Option<&CStr> {
+        unsafe {
+            let ptr = switch_event_get_header_idx(self.0, name.as_ptr(), -1);
+            if ptr.is_null() {
+                None
+            } else {
+                Some(CStr::from_ptr(ptr))
+            }
+        }
+    }
+
+    /// Retrieve the body of an event. See: [`switch_event_get_body`](../../freeswitch_sys/fn.switch_event_get_body.html).
+    pub fn get_body(&self) -> Option<&CStr> {
+        unsafe {
+            let ptr = switch_event_get_body(self.0);
+            if ptr.is_null() {
+                None
+            } else {
+                Some(CStr::from_ptr(ptr))
+            }
+        }
+    }
+
     /// Set the body of an event. See: [`switch_event_set_body`](../../freeswitch_sys/fn.switch_event_set_body.html).
     pub fn set_body<T>(&mut self, body: T) -> Result<()>
     where
@@ -126,6 +192,25 @@ impl Event {
     }
 }
 
+/// An event owned by FS, lent out for the duration of a call, ie the params of an api request.
+pub struct EventRef<'a>(Event, PhantomData<&'a switch_event_t>);
+
+impl EventRef<'_> {
+    /// # Safety
+    ///
+    /// `ptr` must be null or a valid event that outlives the returned ref
+    pub(crate) unsafe fn from_ptr(ptr: *mut switch_event_t) -> Option<Self> {
+        (!ptr.is_null()).then(|| Self(Event(ptr), PhantomData))
+    }
+}
+
+impl Deref for EventRef<'_> {
+    type Target = Event;
+    fn deref(&self) -> &Event {
+        &self.0
+    }
+}
+
 // NOTES:
 // Generally FS will null the ptr once the event is fired or Error'd
 // drop is only really necessary if user create events and DON'T use them ...
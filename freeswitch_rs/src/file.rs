@@ -0,0 +1,260 @@
+use freeswitch_sys::*;
+use std::ffi::c_void;
+use std::ffi::CStr;
+use std::ffi::CString;
+use std::io::SeekFrom;
+use std::marker::PhantomData;
+use std::ptr;
+use std::slice;
+
+use crate::prelude::*;
+
+use crate::Frame;
+
+/// A file format implemented in rust, registered via [`FSModuleInterface::add_file_interface`].
+///
+/// A new instance is created every time FS opens a path matching one of the registered
+/// extensions or url prefixes, and is consumed on close.
+/// Audio is always exchanged as signed 16bit linear samples, interleaved by channel.
+pub trait FileFormat: Sized + Send + 'static {
+    const NAME: &'static str;
+    /// File extensions, or url prefixes ie `myproto` for `myproto://...`, handled by the format.
+    const EXTENSIONS: &'static [&'static str];
+
+    /// Open the path, setting the samplerate and channels of the handle when reading.
+    fn open(handle: &mut FileHandle, path: &str) -> Result<Self>;
+
+    /// Fill the frame with audio, setting its datalen to the bytes read.
+    /// A datalen of zero signals the end of the file.
+    fn read(&mut self, handle: &mut FileHandle, frame: &mut Frame) -> Result<()>;
+
+    fn write(&mut self, _handle: &mut FileHandle, _frame: &Frame) -> Result<()> {
+        Err(switch_status_t::SWITCH_STATUS_NOTIMPL.into())
+    }
+
+    /// Seek to a position measured in samples, returning the new position.
+    fn seek(&mut self, _handle: &mut FileHandle, _pos: SeekFrom) -> Result<u64> {
+        Err(switch_status_t::SWITCH_STATUS_NOTIMPL.into())
+    }
+
+    fn close(self, _handle: &mut FileHandle) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Wrapper around the FS handle for an open file.
+pub struct FileHandle<'a>(*mut switch_file_handle_t, PhantomData<&'a mut ()>);
+
+impl<'a> FileHandle<'a> {
+    /// True if FS opened the file for reading.
+    pub fn is_read(&self) -> bool {
+        unsafe { (*self.0).flags & switch_file_flag_enum_t_SWITCH_FILE_FLAG_READ != 0 }
+    }
+
+    /// True if FS opened the file for writing.
+    pub fn is_write(&self) -> bool {
+        unsafe { (*self.0).flags & switch_file_flag_enum_t_SWITCH_FILE_FLAG_WRITE != 0 }
+    }
+
+    pub fn samplerate(&self) -> u32 {
+        unsafe { (*self.0).samplerate }
+    }
+
+    pub fn set_samplerate(&mut self, rate: u32) {
+        unsafe {
+            (*self.0).samplerate = rate;
+            (*self.0).native_rate = rate;
+        }
+    }
+
+    pub fn channels(&self) -> u32 {
+        unsafe { (*self.0).channels }
+    }
+
+    pub fn set_channels(&mut self, channels: u32) {
+        unsafe { (*self.0).channels = channels }
+    }
+
+    /// Set the total length of the file in samples, if known.
+    pub fn set_samples(&mut self, samples: u32) {
+        unsafe { (*self.0).samples = samples }
+    }
+
+    pub fn set_seekable(&mut self, seekable: bool) {
+        unsafe { (*self.0).seekable = seekable.into() }
+    }
+
+    fn bytes_per_sample(&self) -> usize {
+        2 * self.channels().max(1) as usize
+    }
+}
+
+impl FSModuleInterface {
+    /// Register a file format for its extensions and url prefixes.
+    pub fn add_file_interface<T: FileFormat>(&self) {
+        let t = switch_module_interface_name_t::SWITCH_FILE_INTERFACE;
+        // SAFETY: We assume the module ptr given to us is valid
+        // also we restrict access to the builder to ONLY the load function
+        unsafe {
+            let ptr = freeswitch_sys::switch_loadable_module_create_interface(*self.0, t)
+                as *mut switch_file_interface_t;
+            let interface = &mut *ptr;
+            interface.interface_name = CString::new(T::NAME).unwrap().into_raw();
+            interface.file_open = Some(file_open_raw::<T>);
+            interface.file_close = Some(file_close_raw::<T>);
+            interface.file_read = Some(file_read_raw::<T>);
+            interface.file_write = Some(file_write_raw::<T>);
+            interface.file_seek = Some(file_seek_raw::<T>);
+
+            // FS expects a null terminated list
+            let extens: Vec<*mut ::std::os::raw::c_char> = T::EXTENSIONS
+                .iter()
+                .map(|e| CString::new(*e).unwrap().into_raw())
+                .chain(std::iter::once(ptr::null_mut()))
+                .collect();
+            interface.extens = Box::leak(extens.into_boxed_slice()).as_mut_ptr();
+        }
+    }
+}
+
+unsafe fn private_data<'a, T>(handle: *mut switch_file_handle_t) -> Option<&'a mut T> {
+    handle
+        .as_ref()
+        .and_then(|h| (h.private_info as *mut T).as_mut())
+}
+
+unsafe extern "C" fn file_open_raw<T: FileFormat>(
+    handle: *mut switch_file_handle_t,
+    path: *const ::std::os::raw::c_char,
+) -> switch_status_t {
+    catch_panic("file_open", ptr::null_mut(), || {
+        if handle.is_null() || path.is_null() {
+            return switch_status_t::SWITCH_STATUS_FALSE;
+        }
+        let path = CStr::from_ptr(path).to_string_lossy();
+        let mut fh = FileHandle(handle, PhantomData);
+        match T::open(&mut fh, &path) {
+            Ok(f) => {
+                (*handle).private_info = Box::into_raw(Box::new(f)) as *mut c_void;
+                switch_status_t::SWITCH_STATUS_SUCCESS
+            }
+            Err(e) => e.into(),
+        }
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
+
+unsafe extern "C" fn file_close_raw<T: FileFormat>(
+    handle: *mut switch_file_handle_t,
+) -> switch_status_t {
+    catch_panic("file_close", ptr::null_mut(), || {
+        let Some(h) = handle.as_mut() else {
+            return switch_status_t::SWITCH_STATUS_FALSE;
+        };
+        let ptr = h.private_info as *mut T;
+        h.private_info = ptr::null_mut();
+        if ptr.is_null() {
+            return switch_status_t::SWITCH_STATUS_SUCCESS;
+        }
+        // take back ownership of box so we can clean up
+        let f = Box::from_raw(ptr);
+        let mut fh = FileHandle(handle, PhantomData);
+        match f.close(&mut fh) {
+            Ok(()) => switch_status_t::SWITCH_STATUS_SUCCESS,
+            Err(e) => e.into(),
+        }
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
+
+// len is measured in samples per channel, both in and out
+unsafe extern "C" fn file_read_raw<T: FileFormat>(
+    handle: *mut switch_file_handle_t,
+    data: *mut c_void,
+    len: *mut switch_size_t,
+) -> switch_status_t {
+    catch_panic("file_read", ptr::null_mut(), || {
+        let Some(f) = private_data::<T>(handle) else {
+            return switch_status_t::SWITCH_STATUS_FALSE;
+        };
+        if data.is_null() || len.is_null() {
+            return switch_status_t::SWITCH_STATUS_FALSE;
+        }
+        let mut fh = FileHandle(handle, PhantomData);
+        let bytes_per_sample = fh.bytes_per_sample();
+        let buf = slice::from_raw_parts_mut(data as *mut u8, *len * bytes_per_sample);
+        let mut frame = Frame::new(buf);
+        frame.set_rate(fh.samplerate());
+
+        match f.read(&mut fh, &mut frame) {
+            Ok(()) => {
+                *len = frame.datalen() / bytes_per_sample;
+                if *len == 0 {
+                    switch_status_t::SWITCH_STATUS_FALSE
+                } else {
+                    switch_status_t::SWITCH_STATUS_SUCCESS
+                }
+            }
+            Err(e) => e.into(),
+        }
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
+
+unsafe extern "C" fn file_write_raw<T: FileFormat>(
+    handle: *mut switch_file_handle_t,
+    data: *mut c_void,
+    len: *mut switch_size_t,
+) -> switch_status_t {
+    catch_panic("file_write", ptr::null_mut(), || {
+        let Some(f) = private_data::<T>(handle) else {
+            return switch_status_t::SWITCH_STATUS_FALSE;
+        };
+        if data.is_null() || len.is_null() {
+            return switch_status_t::SWITCH_STATUS_FALSE;
+        }
+        let mut fh = FileHandle(handle, PhantomData);
+        let bytes = *len * fh.bytes_per_sample();
+        let mut frame = Frame::new(slice::from_raw_parts_mut(data as *mut u8, bytes));
+        frame.set_datalen(bytes);
+        frame.set_samples(*len);
+        frame.set_rate(fh.samplerate());
+
+        match f.write(&mut fh, &frame) {
+            Ok(()) => switch_status_t::SWITCH_STATUS_SUCCESS,
+            Err(e) => e.into(),
+        }
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
+
+unsafe extern "C" fn file_seek_raw<T: FileFormat>(
+    handle: *mut switch_file_handle_t,
+    cur_pos: *mut ::std::os::raw::c_uint,
+    samples: i64,
+    whence: ::std::os::raw::c_int,
+) -> switch_status_t {
+    catch_panic("file_seek", ptr::null_mut(), || {
+        let Some(f) = private_data::<T>(handle) else {
+            return switch_status_t::SWITCH_STATUS_FALSE;
+        };
+        let pos = match whence {
+            0 => SeekFrom::Start(samples.max(0) as u64),
+            1 => SeekFrom::Current(samples),
+            2 => SeekFrom::End(samples),
+            _ => return switch_status_t::SWITCH_STATUS_FALSE,
+        };
+        let mut fh = FileHandle(handle, PhantomData);
+        match f.seek(&mut fh, pos) {
+            Ok(n) => {
+                if !cur_pos.is_null() {
+                    *cur_pos = n as ::std::os::raw::c_uint;
+                }
+                (*handle).pos = n as _;
+                switch_status_t::SWITCH_STATUS_SUCCESS
+            }
+            Err(e) => e.into(),
+        }
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
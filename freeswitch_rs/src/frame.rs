@@ -1,6 +1,7 @@
 use crate::types::switch_frame_t;
 use std::ffi::c_void;
 use std::mem;
+use std::slice;
 
 /// A wrapper around FreeSWITCH's `switch_frame_t` structure with an associated buffer.
 ///
@@ -28,8 +29,56 @@ impl<'a> Frame<'a> {
 }
 
 impl<'a> Frame<'a> {
+    /// Wrap a frame owned by FreeSWITCH, borrowing its `datalen` bytes of data.
+    ///
+    /// # Safety
+    ///
+    /// `frame` must be valid and its data must not be freed or modified elsewhere for `'a`
+    pub(crate) unsafe fn from_raw(frame: *const switch_frame_t) -> Self {
+        let f = *frame;
+        let buf: &'a mut [u8] = if f.data.is_null() {
+            &mut []
+        } else {
+            slice::from_raw_parts_mut(f.data as *mut u8, f.datalen as usize)
+        };
+        Self(f, buf)
+    }
+
     /// Returns a reference to the frame's data buffer.
     pub fn data(&'a self) -> &'a [u8] {
         self.1
     }
+
+    /// Returns a mutable reference to the frame's data buffer.
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        self.1
+    }
+
+    /// Length in bytes of the valid data in the buffer.
+    pub fn datalen(&self) -> usize {
+        self.0.datalen as usize
+    }
+
+    /// Set the length of valid data, clamped to the buffer length.
+    pub fn set_datalen(&mut self, len: usize) {
+        self.0.datalen = len.min(self.1.len()) as u32;
+    }
+
+    /// Number of samples (per channel) held by the frame.
+    pub fn samples(&self) -> usize {
+        self.0.samples as usize
+    }
+
+    pub fn set_samples(&mut self, samples: usize) {
+        self.0.samples = samples.min(u32::MAX as usize) as u32;
+    }
+
+    /// Sample rate of the frame's data.
+    pub fn rate(&self) -> u32 {
+        self.0.rate
+    }
+
+    pub fn set_rate(&mut self, rate: u32) {
+        self.0.rate = rate;
+    }
 }
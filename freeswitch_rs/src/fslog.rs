@@ -1,10 +1,23 @@
-use freeswitch_sys::{switch_log_level_t, switch_log_printf, switch_text_channel_t};
+use freeswitch_sys::{
+    switch_bool_t_SWITCH_FALSE, switch_core_session_t, switch_log_bind_logger, switch_log_level_t,
+    switch_log_node_t, switch_log_printf, switch_log_unbind_logger, switch_status_t,
+    switch_text_channel_t,
+};
 use log::Log;
-use std::{ffi::CString, ptr::null};
+use std::{
+    any::Any,
+    ffi::{CStr, CString},
+    panic::{catch_unwind, AssertUnwindSafe},
+    ptr::{null, null_mut},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, PoisonError, RwLock,
+    },
+};
 
 use crate::{
     core::{Session, SessionExt},
-    utils::FSNewType,
+    utils::{FSNewType, Result},
 };
 
 #[repr(transparent)]
@@ -130,3 +143,185 @@ impl Log for FSLogger {
 
     fn flush(&self) {}
 }
+
+/// Log a panic caught at an FFI entry point, see [`catch_panic`](crate::utils::catch_panic).
+///
+/// This goes straight to FS rather than through the `log` facade, which may not be set up
+/// or may have filtered errors out.
+pub(crate) fn log_panic(
+    context: &str,
+    session: *mut switch_core_session_t,
+    payload: &(dyn Any + Send),
+) {
+    let msg = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause");
+    let Ok(msg) = CString::new(format!("panic in {context}: {msg}\n")) else {
+        return;
+    };
+    // FS looks up the uuid for the session channel
+    let channel = if session.is_null() {
+        SWITCH_CHANNEL_ID_LOG
+    } else {
+        SWITCH_CHANNEL_ID_SESSION
+    };
+    unsafe {
+        switch_log_printf(
+            channel.0,
+            c"freeswitch_rs".as_ptr(),
+            null(),
+            0,
+            session as *const ::std::os::raw::c_char,
+            switch_log_level_t::SWITCH_LOG_CRIT,
+            c"%s".as_ptr(),
+            msg.as_ptr(),
+        );
+    }
+}
+
+// ==========
+/// A line from FS's own log stream, see [`bind_logger`].
+#[derive(Debug)]
+pub struct LogRecord<'a> {
+    pub level: switch_log_level_t,
+    pub channel: switch_text_channel_t,
+    pub file: &'a str,
+    pub line: u32,
+    pub func: &'a str,
+    /// The session uuid when logged against a session.
+    pub uuid: Option<&'a str>,
+    /// The message without FS's formatted prefix.
+    pub content: &'a str,
+    /// Microseconds since the epoch.
+    pub timestamp: i64,
+}
+
+type LogFn = dyn Fn(&LogRecord) + Send + Sync;
+
+struct LogBindings {
+    next_id: u64,
+    // Along with whether the logger's panic has been reported
+    loggers: Vec<(u64, switch_log_level_t, Box<LogFn>, AtomicBool)>,
+}
+
+static LOG_BINDINGS: RwLock<LogBindings> = RwLock::new(LogBindings {
+    next_id: 0,
+    loggers: Vec::new(),
+});
+
+// The level our single logger is bound to FS with, if bound.
+// FS holds its bind lock while calling log_raw, which reads LOG_BINDINGS, so (un)binding happens
+// under this lock instead and LOG_BINDINGS is never held while calling into FS.
+static LOG_BOUND: Mutex<Option<switch_log_level_t>> = Mutex::new(None);
+
+/// A registered logger, unbound when dropped.
+///
+/// Drop it before the module unloads.
+pub struct LogBinding(u64);
+
+impl Drop for LogBinding {
+    fn drop(&mut self) {
+        let mut bound = LOG_BOUND.lock().unwrap_or_else(PoisonError::into_inner);
+        let is_empty = {
+            let mut bindings = LOG_BINDINGS.write().unwrap_or_else(PoisonError::into_inner);
+            bindings.loggers.retain(|(id, _, _, _)| *id != self.0);
+            bindings.loggers.is_empty()
+        };
+        if is_empty && bound.take().is_some() {
+            // SAFETY: FS takes its bind lock, so no calls are in flight once this returns
+            unsafe {
+                switch_log_unbind_logger(Some(log_raw));
+            }
+        }
+    }
+}
+
+/// Receive every line FS logs at or above `level`. See: [`switch_log_bind_logger`](../../freeswitch_sys/fn.switch_log_bind_logger.html).
+///
+/// Loggers are called from FS's logging thread, so should hand records off rather than block.
+/// Binding or dropping a logger from within a logger will deadlock.
+///
+/// # Examples
+///
+/// ```
+/// let binding = bind_logger(switch_log_level_t::SWITCH_LOG_WARNING, |record| {
+///     eprintln!("[{:?}] {}:{} {}", record.level, record.file, record.line, record.content);
+/// })?;
+/// ```
+pub fn bind_logger<F>(level: switch_log_level_t, logger: F) -> Result<LogBinding>
+where
+    F: Fn(&LogRecord) + Send + Sync + 'static,
+{
+    let mut bound = LOG_BOUND.lock().unwrap_or_else(PoisonError::into_inner);
+
+    // FS only sends lines at or above the level we bind with, so
+    // rebind whenever a logger wants more than we're receiving
+    if bound.map_or(true, |bound| level.0 > bound.0) {
+        // SAFETY: log_raw only touches the registry, which outlives the module
+        unsafe {
+            if bound.take().is_some() {
+                switch_log_unbind_logger(Some(log_raw));
+            }
+            match switch_log_bind_logger(Some(log_raw), level, switch_bool_t_SWITCH_FALSE) {
+                switch_status_t::SWITCH_STATUS_SUCCESS => *bound = Some(level),
+                other => return Err(other.into()),
+            }
+        }
+    }
+
+    let mut bindings = LOG_BINDINGS.write().unwrap_or_else(PoisonError::into_inner);
+    let id = bindings.next_id;
+    bindings.next_id += 1;
+    bindings
+        .loggers
+        .push((id, level, Box::new(logger), AtomicBool::new(false)));
+    Ok(LogBinding(id))
+}
+
+unsafe extern "C" fn log_raw(
+    node: *const switch_log_node_t,
+    level: switch_log_level_t,
+) -> switch_status_t {
+    let Some(node) = node.as_ref() else {
+        return switch_status_t::SWITCH_STATUS_SUCCESS;
+    };
+    let as_str = |ptr: *const ::std::os::raw::c_char| {
+        (!ptr.is_null()).then(|| CStr::from_ptr(ptr).to_string_lossy())
+    };
+    let file = as_str(node.file.as_ptr()).unwrap_or_default();
+    let func = as_str(node.func.as_ptr()).unwrap_or_default();
+    let uuid = as_str(node.userdata);
+    let content = as_str(node.content).unwrap_or_default();
+    let record = LogRecord {
+        level,
+        channel: node.channel,
+        file: &file,
+        line: node.line,
+        func: &func,
+        uuid: uuid.as_deref(),
+        content: &content,
+        timestamp: node.timestamp,
+    };
+
+    let mut panics = vec![];
+    {
+        let bindings = LOG_BINDINGS.read().unwrap_or_else(PoisonError::into_inner);
+        for (_, max, logger, reported) in &bindings.loggers {
+            if level.0 > max.0 {
+                continue;
+            }
+            // Only reported once, as a logger panicking on every line would otherwise feed itself forever
+            if let Err(payload) = catch_unwind(AssertUnwindSafe(|| logger(&record))) {
+                if !reported.swap(true, Ordering::Relaxed) {
+                    panics.push(payload);
+                }
+            }
+        }
+    }
+    for payload in panics {
+        log_panic("bound logger", null_mut(), &*payload);
+    }
+    switch_status_t::SWITCH_STATUS_SUCCESS
+}
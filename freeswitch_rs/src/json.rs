@@ -0,0 +1,131 @@
+use freeswitch_sys::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::ffi::CStr;
+use std::ffi::CString;
+use std::fmt::Display;
+
+use crate::prelude::*;
+
+use crate::core::Session;
+
+/// Implemented by [`switch_json_api_define`](crate::prelude::switch_json_api_define), which marshals
+/// the cJSON request and reply through serde.
+///
+/// # Examples
+///
+/// ```
+/// #[derive(Deserialize)]
+/// struct Req {
+///     uuid: String,
+/// }
+///
+/// #[derive(Serialize)]
+/// struct Resp {
+///     exists: bool,
+/// }
+///
+/// #[switch_json_api_define(name = "channel_exists", desc = "check a channel exists")]
+/// fn channel_exists(req: Req, _session: Option<&Session>) -> std::result::Result<Resp, FSError> {
+///     let uuid = CString::new(req.uuid).map_err(|_| switch_status_t::SWITCH_STATUS_GENERR)?;
+///     Ok(Resp {
+///         exists: Session::locate(&uuid).is_some(),
+///     })
+/// }
+/// ```
+pub trait JsonApiInterface {
+    const NAME: &'static str;
+    const DESC: &'static str;
+    unsafe extern "C" fn json_fn_raw(
+        json: *const cJSON,
+        session: *mut switch_core_session_t,
+        json_reply: *mut *mut cJSON,
+    ) -> switch_status_t;
+}
+
+impl FSModuleInterface {
+    /// Register a json api, invoked with `json {"command": "<NAME>", "data": {...}}`.
+    pub fn add_json_api<T: JsonApiInterface>(&self, _i: T) {
+        let t = switch_module_interface_name_t::SWITCH_JSON_API_INTERFACE;
+        // SAFETY: We assume the module ptr given to us is valid
+        // also we restrict access to the builder to ONLY the load function
+        unsafe {
+            let ptr = freeswitch_sys::switch_loadable_module_create_interface(*self.0, t)
+                as *mut switch_json_api_interface_t;
+            let interface = &mut *ptr;
+            interface.interface_name = CString::new(T::NAME).unwrap().into_raw();
+            interface.desc = CString::new(T::DESC).unwrap().into_raw();
+            interface.function = Some(T::json_fn_raw);
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorReply {
+    message: String,
+}
+
+/// Marshal the cJSON request into the handler's types and back again.
+/// The request is deserialised from the `data` field of the command.
+#[doc(hidden)]
+pub unsafe fn call_json_api<Req, Resp, E, F>(
+    json: *const cJSON,
+    session: *mut switch_core_session_t,
+    json_reply: *mut *mut cJSON,
+    handler: F,
+) -> switch_status_t
+where
+    Req: DeserializeOwned,
+    Resp: Serialize,
+    E: Display,
+    F: FnOnce(Req, Option<&Session>) -> std::result::Result<Resp, E>,
+{
+    let session = (!session.is_null()).then(|| Session::from_ptr(session));
+    let (status, reply) = match parse_request(json).map(|req| handler(req, session.as_ref())) {
+        Ok(Ok(resp)) => (
+            switch_status_t::SWITCH_STATUS_SUCCESS,
+            serde_json::to_string(&resp),
+        ),
+        Ok(Err(e)) => (
+            switch_status_t::SWITCH_STATUS_FALSE,
+            serde_json::to_string(&ErrorReply {
+                message: e.to_string(),
+            }),
+        ),
+        Err(e) => (
+            switch_status_t::SWITCH_STATUS_FALSE,
+            serde_json::to_string(&ErrorReply {
+                message: format!("invalid request: {e}"),
+            }),
+        ),
+    };
+
+    match reply.ok().and_then(|r| CString::new(r).ok()) {
+        Some(reply) if !json_reply.is_null() => {
+            *json_reply = cJSON_Parse(reply.as_ptr());
+            status
+        }
+        Some(_) => status,
+        None => switch_status_t::SWITCH_STATUS_GENERR,
+    }
+}
+
+unsafe fn parse_request<Req: DeserializeOwned>(json: *const cJSON) -> serde_json::Result<Req> {
+    let mut value = if json.is_null() {
+        serde_json::Value::Null
+    } else {
+        let ptr = cJSON_PrintUnformatted(json);
+        if ptr.is_null() {
+            serde_json::Value::Null
+        } else {
+            let res = serde_json::from_slice(CStr::from_ptr(ptr).to_bytes());
+            free(ptr as *mut ::std::os::raw::c_void);
+            res?
+        }
+    };
+    let data = value
+        .get_mut("data")
+        .map(serde_json::Value::take)
+        .unwrap_or_default();
+    serde_json::from_value(data)
+}
@@ -12,6 +12,7 @@
 //!
 
 // mods
+mod caller;
 mod frame;
 mod modules;
 mod session;
@@ -21,14 +22,28 @@ mod utils;
 pub use frame::*;
 
 // Public mods
+pub mod api;
+pub mod asr;
 pub mod channel;
+pub mod chat;
+pub mod codec;
+pub mod dialplan;
+pub mod endpoint;
 pub mod event;
+pub mod file;
 pub mod fslog;
+pub mod json;
+pub mod limit;
+pub mod say;
+pub mod tts;
+pub mod xml;
 
 pub mod types {
+    pub use freeswitch_sys::cJSON;
     pub use freeswitch_sys::switch_abc_type_t;
     pub use freeswitch_sys::switch_api_interface_t;
     pub use freeswitch_sys::switch_application_interface_t;
+    pub use freeswitch_sys::switch_call_cause_t;
     pub use freeswitch_sys::switch_core_session_t;
     pub use freeswitch_sys::switch_event_types_t;
     pub use freeswitch_sys::switch_frame_t;
@@ -38,6 +53,7 @@ pub mod types {
     pub use freeswitch_sys::switch_module_interface_name_t;
     pub use freeswitch_sys::switch_status_t;
     pub use freeswitch_sys::switch_stream_handle_t;
+    pub use freeswitch_sys::SWITCH_API_VERSION;
 }
 
 pub mod prelude {
@@ -48,6 +64,8 @@ pub mod prelude {
 
     // macros
     pub use freeswitch_rs_macros::switch_api_define;
+    pub use freeswitch_rs_macros::switch_app_define;
+    pub use freeswitch_rs_macros::switch_json_api_define;
     pub use freeswitch_rs_macros::switch_module_define;
 
     // logging
@@ -64,7 +82,11 @@ pub mod prelude {
 #[doc(hidden)]
 pub use modules::*;
 
+#[doc(hidden)]
+pub use utils::catch_panic;
+
 pub mod core {
+    pub use crate::caller::*;
     pub use crate::session::*;
 }
 
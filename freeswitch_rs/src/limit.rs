@@ -0,0 +1,192 @@
+use freeswitch_sys::*;
+use std::ffi::CString;
+use std::ptr;
+
+use crate::prelude::*;
+
+use crate::core::Session;
+
+/// Current usage of a limited resource, see [`LimitBackend::usage`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LimitUsage {
+    /// Calls currently holding the resource.
+    pub usage: i32,
+    /// Calls counted towards the rate within the current interval.
+    pub rate: u32,
+}
+
+/// A `limit` realm implemented in rust, registered via [`FSModuleInterface::add_limit_backend`].
+///
+/// Usable from the dialplan as `limit <NAME> <realm> <resource> [max[/interval]]`
+/// and `limit_execute`. Backends are shared by every call, so state lives in statics or the
+/// module instance.
+///
+/// # Examples
+///
+/// ```
+/// static COUNTS: Mutex<BTreeMap<String, i32>> = Mutex::new(BTreeMap::new());
+///
+/// struct Memory;
+///
+/// impl LimitBackend for Memory {
+///     const NAME: &'static str = "memory";
+///
+///     fn incr(_session: &Session, realm: &str, resource: &str, max: i32, _interval: i32) -> Result<()> {
+///         let mut counts = COUNTS.lock().unwrap();
+///         let count = counts.entry(format!("{realm}_{resource}")).or_default();
+///         if max >= 0 && *count >= max {
+///             return Err(switch_status_t::SWITCH_STATUS_FALSE.into());
+///         }
+///         *count += 1;
+///         Ok(())
+///     }
+///
+///     fn release(_session: &Session, realm: Option<&str>, resource: Option<&str>) -> Result<()> {
+///         if let (Some(realm), Some(resource)) = (realm, resource) {
+///             if let Some(count) = COUNTS.lock().unwrap().get_mut(&format!("{realm}_{resource}")) {
+///                 *count -= 1;
+///             }
+///         }
+///         Ok(())
+///     }
+///
+///     fn usage(realm: &str, resource: &str) -> LimitUsage {
+///         let counts = COUNTS.lock().unwrap();
+///         LimitUsage {
+///             usage: counts.get(&format!("{realm}_{resource}")).copied().unwrap_or(0),
+///             rate: 0,
+///         }
+///     }
+/// }
+/// ```
+pub trait LimitBackend {
+    const NAME: &'static str;
+
+    /// Take the resource for the session. Return an error once `max` is reached, or when more
+    /// than `max` calls arrived within `interval` seconds if an interval is given.
+    /// A negative `max` means no limit, only count.
+    fn incr(session: &Session, realm: &str, resource: &str, max: i32, interval: i32) -> Result<()>;
+
+    /// Give back the resource held by the session.
+    /// Without a realm and resource, release everything the session holds.
+    fn release(session: &Session, realm: Option<&str>, resource: Option<&str>) -> Result<()>;
+
+    fn usage(realm: &str, resource: &str) -> LimitUsage;
+
+    /// Clear all counters.
+    fn reset() -> Result<()> {
+        Err(switch_status_t::SWITCH_STATUS_NOTIMPL.into())
+    }
+
+    /// Clear the rate counter of a resource.
+    fn interval_reset(_realm: &str, _resource: &str) -> Result<()> {
+        Err(switch_status_t::SWITCH_STATUS_NOTIMPL.into())
+    }
+
+    /// Summary of the backend, shown by `limit_status`.
+    fn status() -> String {
+        String::new()
+    }
+}
+
+impl FSModuleInterface {
+    /// Register a limit backend, selectable by `NAME` from `limit` and `limit_execute`.
+    pub fn add_limit_backend<T: LimitBackend>(&self) {
+        let t = switch_module_interface_name_t::SWITCH_LIMIT_INTERFACE;
+        // SAFETY: We assume the module ptr given to us is valid
+        // also we restrict access to the builder to ONLY the load function
+        unsafe {
+            let ptr = freeswitch_sys::switch_loadable_module_create_interface(*self.0, t)
+                as *mut switch_limit_interface_t;
+            let interface = &mut *ptr;
+            interface.interface_name = CString::new(T::NAME).unwrap().into_raw();
+            interface.incr = Some(limit_incr_raw::<T>);
+            interface.release = Some(limit_release_raw::<T>);
+            interface.usage = Some(limit_usage_raw::<T>);
+            interface.reset = Some(limit_reset_raw::<T>);
+            interface.interval_reset = Some(limit_interval_reset_raw::<T>);
+            interface.status = Some(limit_status_raw::<T>);
+        }
+    }
+}
+
+unsafe extern "C" fn limit_incr_raw<T: LimitBackend>(
+    session: *mut switch_core_session_t,
+    realm: *const ::std::os::raw::c_char,
+    resource: *const ::std::os::raw::c_char,
+    max: ::std::os::raw::c_int,
+    interval: ::std::os::raw::c_int,
+) -> switch_status_t {
+    catch_panic("limit_incr", session, || {
+        if session.is_null() {
+            return switch_status_t::SWITCH_STATUS_FALSE;
+        }
+        let s = Session::from_ptr(session);
+        let realm = str_arg(realm).unwrap_or_default();
+        let resource = str_arg(resource).unwrap_or_default();
+        into_status(T::incr(&s, &realm, &resource, max, interval))
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
+
+unsafe extern "C" fn limit_release_raw<T: LimitBackend>(
+    session: *mut switch_core_session_t,
+    realm: *const ::std::os::raw::c_char,
+    resource: *const ::std::os::raw::c_char,
+) -> switch_status_t {
+    catch_panic("limit_release", session, || {
+        if session.is_null() {
+            return switch_status_t::SWITCH_STATUS_FALSE;
+        }
+        let s = Session::from_ptr(session);
+        let realm = str_arg(realm);
+        let resource = str_arg(resource);
+        into_status(T::release(&s, realm.as_deref(), resource.as_deref()))
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
+
+unsafe extern "C" fn limit_usage_raw<T: LimitBackend>(
+    realm: *const ::std::os::raw::c_char,
+    resource: *const ::std::os::raw::c_char,
+    rcount: *mut u32,
+) -> ::std::os::raw::c_int {
+    catch_panic("limit_usage", ptr::null_mut(), || {
+        let realm = str_arg(realm).unwrap_or_default();
+        let resource = str_arg(resource).unwrap_or_default();
+        let usage = T::usage(&realm, &resource);
+        if let Some(rcount) = rcount.as_mut() {
+            *rcount = usage.rate;
+        }
+        usage.usage
+    })
+    .unwrap_or(0)
+}
+
+unsafe extern "C" fn limit_reset_raw<T: LimitBackend>() -> switch_status_t {
+    catch_panic("limit_reset", ptr::null_mut(), || into_status(T::reset()))
+        .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
+
+unsafe extern "C" fn limit_interval_reset_raw<T: LimitBackend>(
+    realm: *const ::std::os::raw::c_char,
+    resource: *const ::std::os::raw::c_char,
+) -> switch_status_t {
+    catch_panic("limit_interval_reset", ptr::null_mut(), || {
+        let realm = str_arg(realm).unwrap_or_default();
+        let resource = str_arg(resource).unwrap_or_default();
+        into_status(T::interval_reset(&realm, &resource))
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
+
+unsafe extern "C" fn limit_status_raw<T: LimitBackend>() -> *mut ::std::os::raw::c_char {
+    catch_panic("limit_status", ptr::null_mut(), || {
+        match CString::new(T::status()) {
+            // Callers free the status with free(), so it must come from the C allocator
+            Ok(status) => strdup(status.as_ptr()),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+    .unwrap_or(ptr::null_mut())
+}
@@ -1,39 +1,67 @@
 use std::ffi::CString;
-use std::io::ErrorKind;
+use std::io::{ErrorKind, Write};
+use std::sync::{Condvar, Mutex, PoisonError, RwLock};
+use std::time::Duration;
 
+use crate::event::EventRef;
 use crate::session::Session;
 use crate::types::*;
-use freeswitch_sys::switch_loadable_module_create_interface;
+use crate::utils::Result;
+use freeswitch_sys::{switch_console_set_complete, switch_loadable_module_create_interface};
 
 #[repr(transparent)]
 pub struct StreamHandle(pub *mut switch_stream_handle_t);
 
+impl StreamHandle {
+    /// Params of the request, ie the query string and HTTP headers when the api is called
+    /// over HTTP by `mod_xml_rpc` or `mod_verto`.
+    pub fn params(&self) -> Option<EventRef<'_>> {
+        // SAFETY: the stream, and its event, outlive the api call
+        unsafe {
+            self.0
+                .as_ref()
+                .and_then(|s| EventRef::from_ptr(s.param_event))
+        }
+    }
+
+    /// Set the `Content-Type` of the HTTP response, which must be done before writing
+    /// anything else. Does nothing when the api wasn't called over HTTP.
+    pub fn set_content_type(&mut self, content_type: &str) -> std::io::Result<()> {
+        if self.params().is_none() {
+            return Ok(());
+        }
+        // The HTTP handlers take headers from the start of the output
+        write!(self, "Content-Type: {content_type}\r\n\r\n")
+    }
+}
+
 impl std::io::Write for StreamHandle {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        unsafe {
-            if self.0.is_null() {
-                return std::io::Result::Err(std::io::Error::new(
-                    ErrorKind::Other,
-                    "No FSStream writer",
-                ));
-            }
-            if let Some(w) = (*self.0).write_function {
-                let cs = CString::new(buf)
-                    .map_err(|_| std::io::Error::new(ErrorKind::InvalidData, ""))?;
-                // The fs writer function success implies the full buffer is written
-                // we don't get any real info on error
-                let res = (w)(self.0, cs.as_ptr());
-                if res == switch_status_t::SWITCH_STATUS_SUCCESS {
-                    Ok(buf.len())
-                } else {
-                    std::io::Result::Err(std::io::Error::new(
-                        ErrorKind::Other,
-                        "FSStream writer Error",
-                    ))
+        let Some(stream) = (unsafe { self.0.as_ref() }) else {
+            return Err(std::io::Error::other("No FSStream writer"));
+        };
+        // The fs writer function success implies the full buffer is written
+        // we don't get any real info on error
+        let res = unsafe {
+            match (stream.raw_write_function, stream.write_function) {
+                // Preferred as it's binary safe
+                (Some(raw), _) => raw(self.0, buf.as_ptr() as *mut u8, buf.len() as _),
+                (None, Some(w)) => {
+                    let cs = CString::new(buf).map_err(|_| {
+                        std::io::Error::new(
+                            ErrorKind::InvalidData,
+                            "FSStream can't write nul bytes without a raw writer",
+                        )
+                    })?;
+                    w(self.0, c"%s".as_ptr(), cs.as_ptr())
                 }
-            } else {
-                std::io::Result::Err(std::io::Error::new(ErrorKind::Other, "No FSStream writer"))
+                (None, None) => return Err(std::io::Error::other("No FSStream writer")),
             }
+        };
+        if res == switch_status_t::SWITCH_STATUS_SUCCESS {
+            Ok(buf.len())
+        } else {
+            Err(std::io::Error::other("FSStream writer Error"))
         }
     }
 
@@ -44,18 +72,129 @@ impl std::io::Write for StreamHandle {
 
 // ========
 
-// We will need a macro to transform trait into extern C functions ....
-// and call RUST function
-pub trait LoadableModule {
-    fn load(module: FSModuleInterface, pool: FSModulePool) -> switch_status_t;
-    fn shutdown() -> switch_status_t {
-        switch_status_t::SWITCH_STATUS_SUCCESS
+/// A module's state, created on load and kept by [`switch_module_define`](crate::prelude::switch_module_define)
+/// until the module unloads.
+///
+/// Handlers can borrow the loaded instance by taking it as their first argument,
+/// see [`ModuleInstance`].
+///
+/// # Examples
+///
+/// ```
+/// #[switch_module_define(mod_counter)]
+/// struct Counter {
+///     calls: AtomicUsize,
+/// }
+///
+/// impl LoadableModule for Counter {
+///     fn load(module: FSModuleInterface, _pool: FSModulePool) -> Result<Self> {
+///         module.add_api(count);
+///         Ok(Counter {
+///             calls: AtomicUsize::new(0),
+///         })
+///     }
+/// }
+///
+/// #[switch_api_define]
+/// fn count(m: &Counter, _cmd: &str, _session: Option<&Session>, mut stream: StreamHandle) -> switch_status_t {
+///     let calls = m.calls.fetch_add(1, Ordering::Relaxed) + 1;
+///     let _ = writeln!(stream, "+OK {calls}");
+///     switch_status_t::SWITCH_STATUS_SUCCESS
+/// }
+/// ```
+pub trait LoadableModule: Sized + Send + Sync + 'static {
+    fn load(module: FSModuleInterface, pool: FSModulePool) -> Result<Self>;
+
+    /// Called as the module unloads, consuming the instance.
+    fn shutdown(self) -> Result<()> {
+        Ok(())
     }
-    fn runtime() -> switch_status_t {
+
+    /// Called repeatedly on the FS managed module thread until it returns anything other than
+    /// `SWITCH_STATUS_SUCCESS`, or the module begins to unload.
+    ///
+    /// The shutdown signal is raised before [`LoadableModule::shutdown`] is called, which waits
+    /// for the runtime to return, so long running loops must check it regularly.
+    /// The default returns `SWITCH_STATUS_TERM`, ending the thread straight away.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// fn runtime(&self, shutdown: &ShutdownSignal) -> switch_status_t {
+    ///     while !shutdown.wait_timeout(Duration::from_secs(10)) {
+    ///         // housekeeping ...
+    ///     }
+    ///     switch_status_t::SWITCH_STATUS_TERM
+    /// }
+    /// ```
+    fn runtime(&self, _shutdown: &ShutdownSignal) -> switch_status_t {
         switch_status_t::SWITCH_STATUS_TERM
     }
 }
 
+/// Access to the loaded module, implemented by [`switch_module_define`](crate::prelude::switch_module_define).
+///
+/// Api and application handlers declared with a leading `&Module` argument are passed the
+/// instance automatically.
+pub trait ModuleInstance: LoadableModule {
+    #[doc(hidden)]
+    fn instance() -> &'static RwLock<Option<Self>>;
+
+    /// Borrow the loaded module, returns `None` if it isn't loaded.
+    ///
+    /// The module can't unload while borrowed, so avoid holding onto it.
+    fn with_module<R>(f: impl FnOnce(&Self) -> R) -> Option<R> {
+        let guard = Self::instance()
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        guard.as_ref().map(f)
+    }
+}
+
+/// Raised when the module is unloading, see [`LoadableModule::runtime`].
+pub struct ShutdownSignal {
+    flag: Mutex<bool>,
+    cvar: Condvar,
+}
+
+impl ShutdownSignal {
+    #[doc(hidden)]
+    pub const fn new() -> Self {
+        Self {
+            flag: Mutex::new(false),
+            cvar: Condvar::new(),
+        }
+    }
+
+    /// Returns true once the module has started to unload.
+    pub fn is_shutdown(&self) -> bool {
+        *self.flag.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Block for up to `timeout`, waking early if the module starts to unload.
+    /// Returns true if the module is unloading.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let guard = self.flag.lock().unwrap_or_else(PoisonError::into_inner);
+        let (guard, _) = self
+            .cvar
+            .wait_timeout_while(guard, timeout, |shutdown| !*shutdown)
+            .unwrap_or_else(PoisonError::into_inner);
+        *guard
+    }
+
+    #[doc(hidden)]
+    pub fn set(&self, state: bool) {
+        *self.flag.lock().unwrap_or_else(PoisonError::into_inner) = state;
+        self.cvar.notify_all();
+    }
+}
+
+impl Default for ShutdownSignal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 // We have to make ptr public to allow macros to create them...
 #[repr(transparent)]
 pub struct FSModuleInterface(pub *mut *mut switch_loadable_module_interface_t);
@@ -73,6 +212,24 @@ impl FSModuleInterface {
         freeswitch_sys::switch_loadable_module_create_module_interface(pool, mod_name)
     }
 
+    // Internally FS locks so safe to use &self
+    pub fn add_application<T: ApplicationInterface>(&self, _i: T) {
+        let t = switch_module_interface_name_t::SWITCH_APPLICATION_INTERFACE;
+        // SAFETY: We assume the module ptr given to us is valid
+        // also we restrict access to the builder to ONLY the load function
+        unsafe {
+            let ptr = switch_loadable_module_create_interface(*self.0, t)
+                as *mut switch_application_interface_t;
+            let interface = &mut *ptr;
+            interface.interface_name = CString::new(T::NAME).unwrap().into_raw();
+            interface.short_desc = CString::new(T::DESC).unwrap().into_raw();
+            interface.long_desc = CString::new(T::DESC).unwrap().into_raw();
+            interface.syntax = CString::new(T::SYNTAX).unwrap().into_raw();
+            interface.flags = T::flags().0;
+            interface.application_function = Some(T::app_fn_raw);
+        }
+    }
+
     // Internally FS locks so safe to use &self
     pub fn add_api<T: ApiInterface>(&self, _i: T) {
         let t = switch_module_interface_name_t::SWITCH_API_INTERFACE;
@@ -84,14 +241,54 @@ impl FSModuleInterface {
             let interface = &mut *ptr;
             interface.interface_name = CString::new(T::NAME).unwrap().into_raw();
             interface.desc = CString::new(T::DESC).unwrap().into_raw();
+            interface.syntax = CString::new(T::SYNTAX).unwrap().into_raw();
             interface.function = Some(T::api_fn_raw);
         }
+        if !T::COMPLETE.is_empty() {
+            for complete in T::COMPLETE {
+                set_console_complete(&format!("add {} {}", T::NAME, complete));
+            }
+            API_COMPLETIONS
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(T::NAME);
+        }
+    }
+
+    /// Remove the console completions registered by [`FSModuleInterface::add_api`],
+    /// called as the module unloads.
+    #[doc(hidden)]
+    pub fn remove_api_completions() {
+        let names = std::mem::take(
+            &mut *API_COMPLETIONS
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+        );
+        for name in names {
+            set_console_complete(&format!("del {name}"));
+        }
+    }
+}
+
+// Apis with console completions, which FS leaves behind unless deleted
+static API_COMPLETIONS: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+fn set_console_complete(line: &str) {
+    if let Ok(line) = CString::new(line) {
+        // SAFETY: FS copies the line into its completion db
+        unsafe {
+            switch_console_set_complete(line.as_ptr());
+        }
     }
 }
 
 pub trait ApiInterface {
     const NAME: &'static str;
     const DESC: &'static str;
+    /// Usage shown by `show api`.
+    const SYNTAX: &'static str = "";
+    /// fs_cli tab completions for the arguments, ie `start ::console::list_uuid`.
+    const COMPLETE: &'static [&'static str] = &[];
     fn api_fn(
         cmd: &str,
         session: Option<&Session>,
@@ -103,3 +300,71 @@ pub trait ApiInterface {
         stream: *mut freeswitch_sys::switch_stream_handle_t,
     ) -> freeswitch_sys::switch_status_t;
 }
+
+pub type ApplicationFlags = freeswitch_sys::switch_application_flag_enum_t;
+
+/// Flags for the module table, set via `switch_module_define(.., flags = ..)`.
+pub type ModuleFlags = freeswitch_sys::switch_module_flag_enum_t;
+
+pub trait ApplicationInterface {
+    const NAME: &'static str;
+    const DESC: &'static str;
+    const SYNTAX: &'static str;
+    fn flags() -> ApplicationFlags {
+        ApplicationFlags::SAF_NONE
+    }
+    fn app_fn(session: &Session, data: &str);
+    unsafe extern "C" fn app_fn_raw(
+        session: *mut freeswitch_sys::switch_core_session_t,
+        data: *const ::std::os::raw::c_char,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe extern "C" fn raw_write(
+        stream: *mut switch_stream_handle_t,
+        data: *mut u8,
+        len: usize,
+    ) -> switch_status_t {
+        let out = &mut *((*stream).data as *mut Vec<u8>);
+        out.extend_from_slice(std::slice::from_raw_parts(data, len));
+        switch_status_t::SWITCH_STATUS_SUCCESS
+    }
+
+    #[test]
+    fn stream_handle_writes_binary_through_raw_writer() {
+        let mut out: Vec<u8> = vec![];
+        // SAFETY: a zeroed stream is one without writers or params
+        let mut stream: switch_stream_handle_t = unsafe { std::mem::zeroed() };
+        stream.data = &mut out as *mut Vec<u8> as *mut _;
+        stream.raw_write_function = Some(raw_write);
+
+        let mut handle = StreamHandle(&mut stream);
+        handle.write_all(b"a\0b%s").unwrap();
+        handle.set_content_type("text/plain").unwrap();
+
+        assert!(handle.params().is_none());
+        assert_eq!(out, b"a\0b%s");
+    }
+
+    #[test]
+    fn set_content_type_writes_header_when_called_over_http() {
+        let mut out: Vec<u8> = vec![];
+        // SAFETY: a zeroed stream is one without writers or params
+        let mut stream: switch_stream_handle_t = unsafe { std::mem::zeroed() };
+        stream.data = &mut out as *mut Vec<u8> as *mut _;
+        stream.raw_write_function = Some(raw_write);
+        // Only checked for null, never read
+        stream.param_event = std::ptr::NonNull::dangling().as_ptr();
+
+        let mut handle = StreamHandle(&mut stream);
+        handle.set_content_type("application/json").unwrap();
+        handle.write_all(b"{}").unwrap();
+
+        assert!(handle.params().is_some());
+        assert_eq!(out, b"Content-Type: application/json\r\n\r\n{}");
+    }
+}
@@ -0,0 +1,147 @@
+use freeswitch_sys::*;
+use std::ffi::CString;
+use std::marker::PhantomData;
+use std::ptr;
+
+use crate::prelude::*;
+
+use crate::core::Session;
+
+pub type SayType = switch_say_type_t;
+pub type SayMethod = switch_say_method_t;
+pub type SayGender = switch_say_gender_t;
+
+/// How FS has asked for the text to be rendered.
+#[derive(Debug, Clone)]
+pub struct SayArgs {
+    pub say_type: SayType,
+    pub method: SayMethod,
+    pub gender: SayGender,
+    /// File extension of the sound prompts to use, if given.
+    pub ext: Option<String>,
+}
+
+/// Input callback args of the playback, honoured by [`InputArgs::play_file`] so callers can
+/// interrupt with dtmf.
+pub struct InputArgs<'a>(*mut switch_input_args_t, PhantomData<&'a mut ()>);
+
+impl<'a> InputArgs<'a> {
+    /// Play a sound file to the session. See: [`switch_ivr_play_file`](../../freeswitch_sys/fn.switch_ivr_play_file.html).
+    ///
+    /// Returns an error status (ie `SWITCH_STATUS_BREAK`) if playback was interrupted, which should be
+    /// passed back up to FS.
+    pub fn play_file(&mut self, session: &Session, path: &str) -> Result<()> {
+        let path = CString::new(path).map_err(|_e| switch_status_t::SWITCH_STATUS_GENERR)?;
+        unsafe {
+            match switch_ivr_play_file(session.as_ptr(), ptr::null_mut(), path.as_ptr(), self.0) {
+                switch_status_t::SWITCH_STATUS_SUCCESS => Ok(()),
+                other => Err(other.into()),
+            }
+        }
+    }
+}
+
+/// Language specific `say` rules implemented in rust, registered via
+/// [`FSModuleInterface::add_say_interface`]. `NAME` is the language, ie `en`.
+///
+/// # Examples
+///
+/// ```
+/// struct SayEn;
+///
+/// impl SayInterface for SayEn {
+///     const NAME: &'static str = "en";
+///
+///     fn say(session: &Session, text: &str, args: &SayArgs, input: &mut InputArgs) -> Result<()> {
+///         for digit in text.chars().filter(char::is_ascii_digit) {
+///             input.play_file(session, &format!("digits/{digit}.wav"))?;
+///         }
+///         Ok(())
+///     }
+/// }
+/// ```
+pub trait SayInterface {
+    const NAME: &'static str;
+
+    fn say(session: &Session, text: &str, args: &SayArgs, input: &mut InputArgs) -> Result<()>;
+
+    /// Render the text as a string of prompts instead of playing them.
+    fn say_string(_session: Option<&Session>, _text: &str, _args: &SayArgs) -> Result<String> {
+        Err(switch_status_t::SWITCH_STATUS_NOTIMPL.into())
+    }
+}
+
+impl FSModuleInterface {
+    /// Register say rules for a language.
+    pub fn add_say_interface<T: SayInterface>(&self) {
+        let t = switch_module_interface_name_t::SWITCH_SAY_INTERFACE;
+        // SAFETY: We assume the module ptr given to us is valid
+        // also we restrict access to the builder to ONLY the load function
+        unsafe {
+            let ptr = freeswitch_sys::switch_loadable_module_create_interface(*self.0, t)
+                as *mut switch_say_interface_t;
+            let interface = &mut *ptr;
+            interface.interface_name = CString::new(T::NAME).unwrap().into_raw();
+            interface.say_function = Some(say_raw::<T>);
+            interface.say_string_function = Some(say_string_raw::<T>);
+        }
+    }
+}
+
+unsafe fn say_args(args: *const switch_say_args_t) -> Option<SayArgs> {
+    args.as_ref().map(|a| SayArgs {
+        say_type: a.type_,
+        method: a.method,
+        gender: a.gender,
+        ext: str_arg(a.ext).map(|e| e.into_owned()),
+    })
+}
+
+unsafe extern "C" fn say_raw<T: SayInterface>(
+    session: *mut switch_core_session_t,
+    tosay: *mut ::std::os::raw::c_char,
+    say_args_ptr: *mut switch_say_args_t,
+    args: *mut switch_input_args_t,
+) -> switch_status_t {
+    catch_panic("say", session, || {
+        let Some(say_args) = say_args(say_args_ptr) else {
+            return switch_status_t::SWITCH_STATUS_FALSE;
+        };
+        if session.is_null() {
+            return switch_status_t::SWITCH_STATUS_FALSE;
+        }
+        let s = Session::from_ptr(session);
+        let text = str_arg(tosay).unwrap_or_default();
+        let mut input = InputArgs(args, PhantomData);
+        into_status(T::say(&s, &text, &say_args, &mut input))
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
+
+unsafe extern "C" fn say_string_raw<T: SayInterface>(
+    session: *mut switch_core_session_t,
+    tosay: *mut ::std::os::raw::c_char,
+    say_args_ptr: *mut switch_say_args_t,
+    rstr: *mut *mut ::std::os::raw::c_char,
+) -> switch_status_t {
+    catch_panic("say_string", session, || {
+        let Some(say_args) = say_args(say_args_ptr) else {
+            return switch_status_t::SWITCH_STATUS_FALSE;
+        };
+        if rstr.is_null() {
+            return switch_status_t::SWITCH_STATUS_FALSE;
+        }
+        let s = (!session.is_null()).then(|| Session::from_ptr(session));
+        let text = str_arg(tosay).unwrap_or_default();
+        match T::say_string(s.as_ref(), &text, &say_args).map(CString::new) {
+            Ok(Ok(res)) => {
+                // Callers free the result with free(), so it must come from the C allocator
+                *rstr = strdup(res.as_ptr());
+                switch_status_t::SWITCH_STATUS_SUCCESS
+            }
+            Ok(Err(_)) => switch_status_t::SWITCH_STATUS_GENERR,
+            Err(e) => e.into(),
+        }
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
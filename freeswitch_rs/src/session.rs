@@ -158,6 +158,34 @@ impl Session {
 
 // =====
 
+fs_session_owned_type!(SessionMessage, *mut switch_core_session_message_t);
+
+impl<'a> SessionMessage<'a> {
+    /// The type of message being delivered to the session.
+    pub fn message_id(&self) -> switch_core_session_message_types_t {
+        unsafe { (*self.0).message_id }
+    }
+
+    /// Optional string argument attached to the message.
+    pub fn string_arg(&self) -> Option<&CStr> {
+        unsafe {
+            let ptr = (*self.0).string_arg;
+            if ptr.is_null() {
+                None
+            } else {
+                Some(CStr::from_ptr(ptr))
+            }
+        }
+    }
+
+    /// Numeric argument attached to the message.
+    pub fn numeric_arg(&self) -> i32 {
+        unsafe { (*self.0).numeric_arg }
+    }
+}
+
+// =====
+
 pub type MediaBugFlags = freeswitch_sys::switch_media_bug_flag_enum_t;
 
 fs_session_owned_type!(MediaBug, *mut switch_media_bug_t);
@@ -177,20 +205,23 @@ where
     F: FnMut(&mut MediaBug, switch_abc_type_t) -> bool,
 {
     let callback_ptr = arg2 as *mut F;
-    let callback = &mut *callback_ptr;
-    let mut bug = MediaBug::from_ptr(arg1);
-
-    let res = if callback(&mut bug, arg3) {
-        switch_bool_t_SWITCH_TRUE
-    } else {
-        switch_bool_t_SWITCH_FALSE
-    };
-
-    // take back ownership of box so we can clean up
+    let session = switch_core_media_bug_get_session(arg1);
+    let res = catch_panic("media bug", session, || {
+        let callback = &mut *callback_ptr;
+        let mut bug = MediaBug::from_ptr(arg1);
+        callback(&mut bug, arg3)
+    });
+
+    // take back ownership of box so we can clean up,
+    // FS still sends the close after a panic prunes the bug
     if arg3 == switch_abc_type_t::SWITCH_ABC_TYPE_CLOSE {
-        let _ = Box::from_raw(arg2);
+        catch_panic("media bug", session, || drop(Box::from_raw(callback_ptr)));
+    }
+    match res {
+        Some(true) => switch_bool_t_SWITCH_TRUE,
+        // A panicked bug is pruned
+        Some(false) | None => switch_bool_t_SWITCH_FALSE,
     }
-    res
 }
 
 impl<'a> MediaBug<'a> {
@@ -0,0 +1,219 @@
+use freeswitch_sys::*;
+use std::ffi::c_void;
+use std::ffi::CString;
+use std::ptr;
+use std::slice;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::prelude::*;
+
+use crate::Frame;
+
+/// Outcome of [`SpeechSynthesizer::read`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpeechRead {
+    /// Audio has been written to the frame.
+    Audio,
+    /// No audio is ready yet, FS will poll again.
+    Pending,
+    /// All text has been synthesised.
+    Done,
+}
+
+/// A speech synthesiser implemented in rust, registered via [`FSModuleInterface::add_tts_interface`].
+///
+/// A new instance is created per speech handle, ie per `speak` on a channel.
+/// Text is fed and audio pulled from the channel thread, but flush and params can
+/// arrive elsewhere, so calls are serialised through a lock around the instance.
+pub trait SpeechSynthesizer: Sized + Send + 'static {
+    const NAME: &'static str;
+
+    fn open(voice: Option<&str>, rate: u32, channels: u32) -> Result<Self>;
+
+    /// Queue text to be synthesised.
+    fn feed(&mut self, text: &str) -> Result<()>;
+
+    /// Fill the frame with signed 16bit linear audio, setting its datalen to the bytes written.
+    /// When `blocking` is set, wait for audio to become available rather than returning pending.
+    fn read(&mut self, frame: &mut Frame, blocking: bool) -> Result<SpeechRead>;
+
+    /// Discard any queued text and audio.
+    fn flush(&mut self) {}
+
+    fn text_param(&mut self, _param: &str, _val: &str) {}
+
+    fn numeric_param(&mut self, _param: &str, _val: i32) {}
+
+    fn float_param(&mut self, _param: &str, _val: f64) {}
+
+    fn close(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl FSModuleInterface {
+    /// Register a speech synthesiser for use with `speak` and `say` style playback.
+    pub fn add_tts_interface<T: SpeechSynthesizer>(&self) {
+        let t = switch_module_interface_name_t::SWITCH_SPEECH_INTERFACE;
+        // SAFETY: We assume the module ptr given to us is valid
+        // also we restrict access to the builder to ONLY the load function
+        unsafe {
+            let ptr = freeswitch_sys::switch_loadable_module_create_interface(*self.0, t)
+                as *mut switch_speech_interface_t;
+            let interface = &mut *ptr;
+            interface.interface_name = CString::new(T::NAME).unwrap().into_raw();
+            interface.speech_open = Some(speech_open_raw::<T>);
+            interface.speech_close = Some(speech_close_raw::<T>);
+            interface.speech_feed_tts = Some(speech_feed_tts_raw::<T>);
+            interface.speech_read_tts = Some(speech_read_tts_raw::<T>);
+            interface.speech_flush_tts = Some(speech_flush_tts_raw::<T>);
+            interface.speech_text_param_tts = Some(speech_text_param_tts_raw::<T>);
+            interface.speech_numeric_param_tts = Some(speech_numeric_param_tts_raw::<T>);
+            interface.speech_float_param_tts = Some(speech_float_param_tts_raw::<T>);
+        }
+    }
+}
+
+unsafe fn synthesizer<'a, T>(sh: *mut switch_speech_handle_t) -> Option<MutexGuard<'a, T>> {
+    sh.as_ref()
+        .and_then(|h| (h.private_info as *const Mutex<T>).as_ref())
+        .map(|m| m.lock().unwrap_or_else(PoisonError::into_inner))
+}
+
+unsafe extern "C" fn speech_open_raw<T: SpeechSynthesizer>(
+    sh: *mut switch_speech_handle_t,
+    voice_name: *const ::std::os::raw::c_char,
+    rate: ::std::os::raw::c_int,
+    channels: ::std::os::raw::c_int,
+    _flags: *mut switch_speech_flag_t,
+) -> switch_status_t {
+    catch_panic("speech_open", ptr::null_mut(), || {
+        if sh.is_null() {
+            return switch_status_t::SWITCH_STATUS_FALSE;
+        }
+        let voice = str_arg(voice_name);
+        match T::open(voice.as_deref(), rate.max(0) as u32, channels.max(1) as u32) {
+            Ok(s) => {
+                (*sh).private_info = Box::into_raw(Box::new(Mutex::new(s))) as *mut c_void;
+                switch_status_t::SWITCH_STATUS_SUCCESS
+            }
+            Err(e) => e.into(),
+        }
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
+
+unsafe extern "C" fn speech_close_raw<T: SpeechSynthesizer>(
+    sh: *mut switch_speech_handle_t,
+    _flags: *mut switch_speech_flag_t,
+) -> switch_status_t {
+    catch_panic("speech_close", ptr::null_mut(), || {
+        let Some(h) = sh.as_mut() else {
+            return switch_status_t::SWITCH_STATUS_FALSE;
+        };
+        let ptr = h.private_info as *mut Mutex<T>;
+        h.private_info = ptr::null_mut();
+        if ptr.is_null() {
+            return switch_status_t::SWITCH_STATUS_SUCCESS;
+        }
+        // take back ownership of box so we can clean up
+        let s = Box::from_raw(ptr)
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+        into_status(s.close())
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
+
+unsafe extern "C" fn speech_feed_tts_raw<T: SpeechSynthesizer>(
+    sh: *mut switch_speech_handle_t,
+    text: *mut ::std::os::raw::c_char,
+    _flags: *mut switch_speech_flag_t,
+) -> switch_status_t {
+    catch_panic("speech_feed_tts", ptr::null_mut(), || {
+        let Some(mut s) = synthesizer::<T>(sh) else {
+            return switch_status_t::SWITCH_STATUS_FALSE;
+        };
+        let text = str_arg(text).unwrap_or_default();
+        into_status(s.feed(&text))
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
+
+// datalen holds the size of the buffer on entry and the bytes written on exit
+unsafe extern "C" fn speech_read_tts_raw<T: SpeechSynthesizer>(
+    sh: *mut switch_speech_handle_t,
+    data: *mut c_void,
+    datalen: *mut switch_size_t,
+    flags: *mut switch_speech_flag_t,
+) -> switch_status_t {
+    catch_panic("speech_read_tts", ptr::null_mut(), || {
+        let Some(mut s) = synthesizer::<T>(sh) else {
+            return switch_status_t::SWITCH_STATUS_FALSE;
+        };
+        if data.is_null() || datalen.is_null() {
+            return switch_status_t::SWITCH_STATUS_FALSE;
+        }
+        let blocking =
+            !flags.is_null() && *flags & switch_speech_flag_enum_t_SWITCH_SPEECH_FLAG_BLOCKING != 0;
+        let mut frame = Frame::new(slice::from_raw_parts_mut(data as *mut u8, *datalen));
+        frame.set_rate((*sh).rate);
+
+        let res = s.read(&mut frame, blocking);
+        *datalen = match res {
+            Ok(SpeechRead::Audio) => frame.datalen(),
+            _ => 0,
+        };
+        match res {
+            Ok(SpeechRead::Audio) => switch_status_t::SWITCH_STATUS_SUCCESS,
+            Ok(SpeechRead::Pending) => switch_status_t::SWITCH_STATUS_BREAK,
+            Ok(SpeechRead::Done) => switch_status_t::SWITCH_STATUS_FALSE,
+            Err(e) => e.into(),
+        }
+    })
+    .unwrap_or(switch_status_t::SWITCH_STATUS_FALSE)
+}
+
+unsafe extern "C" fn speech_flush_tts_raw<T: SpeechSynthesizer>(sh: *mut switch_speech_handle_t) {
+    catch_panic("speech_flush_tts", ptr::null_mut(), || {
+        if let Some(mut s) = synthesizer::<T>(sh) {
+            s.flush();
+        }
+    });
+}
+
+unsafe extern "C" fn speech_text_param_tts_raw<T: SpeechSynthesizer>(
+    sh: *mut switch_speech_handle_t,
+    param: *mut ::std::os::raw::c_char,
+    val: *const ::std::os::raw::c_char,
+) {
+    catch_panic("speech_text_param_tts", ptr::null_mut(), || {
+        if let (Some(mut s), Some(param)) = (synthesizer::<T>(sh), str_arg(param)) {
+            s.text_param(&param, &str_arg(val).unwrap_or_default());
+        }
+    });
+}
+
+unsafe extern "C" fn speech_numeric_param_tts_raw<T: SpeechSynthesizer>(
+    sh: *mut switch_speech_handle_t,
+    param: *mut ::std::os::raw::c_char,
+    val: ::std::os::raw::c_int,
+) {
+    catch_panic("speech_numeric_param_tts", ptr::null_mut(), || {
+        if let (Some(mut s), Some(param)) = (synthesizer::<T>(sh), str_arg(param)) {
+            s.numeric_param(&param, val);
+        }
+    });
+}
+
+unsafe extern "C" fn speech_float_param_tts_raw<T: SpeechSynthesizer>(
+    sh: *mut switch_speech_handle_t,
+    param: *mut ::std::os::raw::c_char,
+    val: f64,
+) {
+    catch_panic("speech_float_param_tts", ptr::null_mut(), || {
+        if let (Some(mut s), Some(param)) = (synthesizer::<T>(sh), str_arg(param)) {
+            s.float_param(&param, val);
+        }
+    });
+}
@@ -1,4 +1,7 @@
-use freeswitch_sys::switch_status_t;
+use freeswitch_sys::{switch_core_session_t, switch_status_t};
+use std::borrow::Cow;
+use std::ffi::CStr;
+use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::{error::Error, fmt::Display};
 
 pub trait FSNewType {
@@ -16,6 +19,11 @@ impl From<switch_status_t> for FSError {
         Self(value)
     }
 }
+impl FSError {
+    pub fn status(&self) -> switch_status_t {
+        self.0
+    }
+}
 impl From<FSError> for switch_status_t {
     fn from(value: FSError) -> Self {
         value.0
@@ -32,6 +40,46 @@ impl Error for FSError {}
 
 pub type Result<T> = std::result::Result<T, FSError>;
 
+pub(crate) fn into_status(res: Result<()>) -> switch_status_t {
+    match res {
+        Ok(()) => switch_status_t::SWITCH_STATUS_SUCCESS,
+        Err(e) => e.into(),
+    }
+}
+
+/// Borrow a nullable C string argument from FS.
+///
+/// # Safety
+///
+/// `ptr` must be null or a valid nul terminated string that outlives `'a`
+pub(crate) unsafe fn str_arg<'a>(ptr: *const ::std::os::raw::c_char) -> Option<Cow<'a, str>> {
+    if ptr.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(ptr).to_string_lossy())
+    }
+}
+
+/// Call rust code from an FFI entry point, stopping any panic from unwinding into FS.
+///
+/// A panic is logged against the session when there is one, and `None` returned so the
+/// caller can hand FS a failure instead.
+#[doc(hidden)]
+pub fn catch_panic<R>(
+    context: &str,
+    session: *mut switch_core_session_t,
+    f: impl FnOnce() -> R,
+) -> Option<R> {
+    // Whatever the closure was part way through is abandoned, so its state is never observed again
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(res) => Some(res),
+        Err(payload) => {
+            crate::fslog::log_panic(context, session, &*payload);
+            None
+        }
+    }
+}
+
 // ---------
 macro_rules! call_with_meta_suffix {
      ($func:ident, $($arg:expr),*) => {{
@@ -0,0 +1,243 @@
+use freeswitch_sys::*;
+use std::ffi::c_void;
+use std::ffi::CString;
+use std::fmt::{self, Display, Write};
+use std::ptr;
+
+use crate::prelude::*;
+
+use crate::event::Event;
+
+pub type XmlSection = switch_xml_section_enum_t;
+
+/// A lookup FS is performing, ie a directory search for a user during registration.
+pub struct XmlRequest<'a> {
+    /// The section being searched, ie `directory`, `dialplan`, `configuration` or `phrases`.
+    pub section: &'a str,
+    pub tag_name: Option<&'a str>,
+    pub key_name: Option<&'a str>,
+    pub key_value: Option<&'a str>,
+    /// Details of the lookup, ie the `user` and `domain` being authenticated.
+    pub params: Option<&'a Event>,
+}
+
+/// An xml element with its attributes and children, escaped when rendered.
+///
+/// # Examples
+///
+/// ```
+/// let domain = XmlBuilder::new("domain").attr("name", "example.com").child(
+///     XmlBuilder::new("user").attr("id", "1000").child(
+///         XmlBuilder::new("params").child(
+///             XmlBuilder::new("param")
+///                 .attr("name", "password")
+///                 .attr("value", "<secret>"),
+///         ),
+///     ),
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XmlBuilder {
+    name: String,
+    attrs: Vec<(String, String)>,
+    children: Vec<XmlBuilder>,
+    text: Option<String>,
+}
+
+impl XmlBuilder {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            attrs: vec![],
+            children: vec![],
+            text: None,
+        }
+    }
+
+    pub fn attr(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.attrs.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn child(mut self, child: XmlBuilder) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    pub fn children(mut self, children: impl IntoIterator<Item = XmlBuilder>) -> Self {
+        self.children.extend(children);
+        self
+    }
+
+    /// Set the text content, rendered before any children.
+    pub fn text(mut self, text: impl Into<String>) -> Self {
+        self.text = Some(text.into());
+        self
+    }
+}
+
+fn escape(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    for c in s.chars() {
+        match c {
+            '&' => f.write_str("&amp;")?,
+            '<' => f.write_str("&lt;")?,
+            '>' => f.write_str("&gt;")?,
+            '"' => f.write_str("&quot;")?,
+            '\'' => f.write_str("&apos;")?,
+            c => f.write_char(c)?,
+        }
+    }
+    Ok(())
+}
+
+impl Display for XmlBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<{}", self.name)?;
+        for (name, value) in &self.attrs {
+            write!(f, " {name}=\"")?;
+            escape(f, value)?;
+            f.write_char('"')?;
+        }
+        if self.text.is_none() && self.children.is_empty() {
+            return f.write_str("/>");
+        }
+        f.write_char('>')?;
+        if let Some(text) = &self.text {
+            escape(f, text)?;
+        }
+        for child in &self.children {
+            write!(f, "{child}")?;
+        }
+        write!(f, "</{}>", self.name)
+    }
+}
+
+type SearchFn = dyn Fn(&XmlRequest) -> Option<XmlBuilder> + Send + Sync;
+
+/// A registered xml search, unbound when dropped.
+///
+/// Keep it alive for as long as the module should serve xml and drop it before the module unloads.
+pub struct XmlBinding {
+    binding: *mut switch_xml_binding_t,
+    _search: Box<Box<SearchFn>>,
+}
+
+// SAFETY: the binding ptr is only used to unbind and the search fn is Send + Sync
+unsafe impl Send for XmlBinding {}
+unsafe impl Sync for XmlBinding {}
+
+impl Drop for XmlBinding {
+    fn drop(&mut self) {
+        // SAFETY: FS takes the xml lock to unbind, so no lookups are in flight once this returns
+        unsafe {
+            switch_xml_unbind_search_function(&mut self.binding);
+        }
+    }
+}
+
+/// Serve xml for the given sections from a closure. See: [`switch_xml_bind_search_function_ret`](../../freeswitch_sys/fn.switch_xml_bind_search_function_ret.html).
+///
+/// The closure returns the content of the requested section, which is wrapped in the
+/// `document` and `section` elements FS expects. Returning `None` passes the lookup on to the
+/// next binding, falling back to the static xml.
+///
+/// # Examples
+///
+/// ```
+/// let binding = bind_xml_search(XmlSection::SWITCH_XML_SECTION_DIRECTORY, |req| {
+///     let params = req.params?;
+///     let user = params.get_header(c"user")?.to_str().ok()?;
+///     let domain = params.get_header(c"domain")?.to_str().ok()?;
+///     Some(
+///         XmlBuilder::new("domain")
+///             .attr("name", domain)
+///             .child(XmlBuilder::new("user").attr("id", user)),
+///     )
+/// })?;
+/// ```
+pub fn bind_xml_search<F>(sections: XmlSection, search: F) -> Result<XmlBinding>
+where
+    F: Fn(&XmlRequest) -> Option<XmlBuilder> + Send + Sync + 'static,
+{
+    let search: Box<Box<SearchFn>> = Box::new(Box::new(search));
+    let mut binding = ptr::null_mut();
+    // SAFETY: the search fn is boxed so its address is stable until the binding is dropped
+    unsafe {
+        let user_data = &*search as *const Box<SearchFn> as *mut c_void;
+        match switch_xml_bind_search_function_ret(
+            Some(xml_search_raw),
+            sections.0,
+            user_data,
+            &mut binding,
+        ) {
+            switch_status_t::SWITCH_STATUS_SUCCESS => Ok(XmlBinding {
+                binding,
+                _search: search,
+            }),
+            other => Err(other.into()),
+        }
+    }
+}
+
+unsafe extern "C" fn xml_search_raw(
+    section: *const ::std::os::raw::c_char,
+    tag_name: *const ::std::os::raw::c_char,
+    key_name: *const ::std::os::raw::c_char,
+    key_value: *const ::std::os::raw::c_char,
+    params: *mut switch_event_t,
+    user_data: *mut c_void,
+) -> switch_xml_t {
+    catch_panic("xml_search", ptr::null_mut(), || {
+        let Some(search) = (user_data as *const Box<SearchFn>).as_ref() else {
+            return ptr::null_mut();
+        };
+        let section = str_arg(section).unwrap_or_default();
+        let tag_name = str_arg(tag_name);
+        let key_name = str_arg(key_name);
+        let key_value = str_arg(key_value);
+        // The event remains owned by FS, we only lend it out
+        let params = (!params.is_null()).then(|| Event(params));
+        let req = XmlRequest {
+            section: &section,
+            tag_name: tag_name.as_deref(),
+            key_name: key_name.as_deref(),
+            key_value: key_value.as_deref(),
+            params: params.as_ref(),
+        };
+
+        let Some(content) = search(&req) else {
+            return ptr::null_mut();
+        };
+        let document = XmlBuilder::new("document")
+            .attr("type", "freeswitch/xml")
+            .child(
+                XmlBuilder::new("section")
+                    .attr("name", &*section)
+                    .child(content),
+            );
+        let Ok(xml) = CString::new(document.to_string()) else {
+            return ptr::null_mut();
+        };
+        // FS parses a copy of the string, and owns the parsed result
+        switch_xml_parse_str_dynamic(xml.as_ptr() as *mut _, switch_bool_t_SWITCH_TRUE)
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xml_builder_escapes_attrs_and_text() {
+        let xml = XmlBuilder::new("param")
+            .attr("value", "\"a\" & 'b'")
+            .child(XmlBuilder::new("text").text("<tag>"))
+            .child(XmlBuilder::new("empty"));
+
+        assert_eq!(
+            xml.to_string(),
+            "<param value=\"&quot;a&quot; &amp; &apos;b&apos;\"><text>&lt;tag&gt;</text><empty/></param>"
+        );
+    }
+}
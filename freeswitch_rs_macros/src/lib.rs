@@ -1,27 +1,131 @@
 use proc_macro::TokenStream;
-use quote::{format_ident, quote};
+use quote::{format_ident, quote, quote_spanned};
 use syn::meta::ParseNestedMeta;
-use syn::parse::Parser;
+use syn::spanned::Spanned;
 use syn::{parse_macro_input, LitStr};
 
+#[derive(Default)]
+struct ModuleAttributes {
+    name: Option<syn::Ident>,
+    flags: Option<syn::Expr>,
+    log_level: Option<LitStr>,
+    api_version: Option<syn::LitInt>,
+    apis: Vec<syn::Expr>,
+    apps: Vec<syn::Expr>,
+    events: Vec<syn::Expr>,
+}
+
+impl ModuleAttributes {
+    fn parse(&mut self, meta: ParseNestedMeta) -> syn::parse::Result<()> {
+        if meta.path.is_ident("flags") {
+            self.flags = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("log_level") {
+            let level: LitStr = meta.value()?.parse()?;
+            match level.value().as_str() {
+                "off" | "error" | "warn" | "info" | "debug" | "trace" => {
+                    self.log_level = Some(level);
+                    Ok(())
+                }
+                _ => Err(syn::Error::new(
+                    level.span(),
+                    "expected one of off, error, warn, info, debug or trace",
+                )),
+            }
+        } else if meta.path.is_ident("api_version") {
+            self.api_version = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("apis") {
+            self.apis = parse_list(&meta)?;
+            Ok(())
+        } else if meta.path.is_ident("apps") {
+            self.apps = parse_list(&meta)?;
+            Ok(())
+        } else if meta.path.is_ident("events") {
+            self.events = parse_list(&meta)?;
+            Ok(())
+        } else if self.name.is_none() {
+            // The module name is the leading bare ident
+            self.name = Some(meta.path.require_ident()?.clone());
+            Ok(())
+        } else {
+            Err(meta.error("unsupported property"))
+        }
+    }
+}
+
+fn parse_list(meta: &ParseNestedMeta) -> syn::parse::Result<Vec<syn::Expr>> {
+    let list: syn::ExprArray = meta.value()?.parse()?;
+    Ok(list.elems.into_iter().collect())
+}
+
+/// Generate the module table and load/shutdown wrappers for a [`LoadableModule`] struct.
+///
+/// `#[switch_module_define(mod_name, flags = .., log_level = "..", api_version = ..)]`
+///
+/// - `flags`: `ModuleFlags` for the table, ie `ModuleFlags::SMODF_GLOBAL_SYMBOLS`
+/// - `log_level`: the initial filter for rust logs, defaults to `"debug"`
+/// - `api_version`: asserted at compile time against the headers `freeswitch_sys` was generated from
+/// - `apis`, `apps`: handlers to register before `load` is called
+/// - `events`: custom event subclasses to reserve on load and free on shutdown
+///
+/// [`LoadableModule`]: ../freeswitch_rs/trait.LoadableModule.html
 #[proc_macro_attribute]
 pub fn switch_module_define(attr: TokenStream, item: TokenStream) -> TokenStream {
     let mod_struct = syn::parse_macro_input!(item as syn::ItemStruct);
-    let args = syn::punctuated::Punctuated::<syn::Path, syn::Token![,]>::parse_terminated
-        .parse2(attr.into())
-        .unwrap();
-
-    let mod_name = args
-        .get(0)
-        .and_then(|p| p.get_ident())
-        .unwrap_or(&mod_struct.ident);
-    impl_switch_module_define(&mod_struct, &mod_name)
+    let mut attrs = ModuleAttributes::default();
+    let parser = syn::meta::parser(|meta| attrs.parse(meta));
+    parse_macro_input!(attr with parser);
+    impl_switch_module_define(&mod_struct, attrs).unwrap_or_else(|e| e.to_compile_error().into())
 }
 
-fn impl_switch_module_define(ast: &syn::ItemStruct, mod_name: &syn::Ident) -> TokenStream {
+fn impl_switch_module_define(
+    ast: &syn::ItemStruct,
+    attrs: ModuleAttributes,
+) -> syn::Result<TokenStream> {
+    // The instance is kept in a static, so must be a concrete type
+    if !ast.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &ast.generics,
+            "module structs can't be generic",
+        ));
+    }
     let struct_name = &ast.ident;
+    let mod_name = attrs.name.as_ref().unwrap_or(struct_name);
     let mod_interface_ident = format_ident!("{}_module_interface", mod_name);
     let mod_name_string = mod_name.to_string().to_owned();
+    let load_context = format!("{mod_name_string} load");
+    let shutdown_context = format!("{mod_name_string} shutdown");
+    let runtime_context = format!("{mod_name_string} runtime");
+
+    let apis = &attrs.apis;
+    let apps = &attrs.apps;
+    let events = &attrs.events;
+    let flags = attrs
+        .flags
+        .map(|flags| quote! { (#flags).0 })
+        .unwrap_or(quote! { 0 });
+    let log_level = format_ident!(
+        "{}",
+        match attrs.log_level.map(|l| l.value()).as_deref() {
+            Some("off") => "Off",
+            Some("error") => "Error",
+            Some("warn") => "Warn",
+            Some("info") => "Info",
+            Some("trace") => "Trace",
+            _ => "Debug",
+        }
+    );
+    // The version FS checks the table against, must match the headers we were generated from
+    let api_version = match attrs.api_version {
+        Some(version) => quote_spanned! {version.span()=>
+            const _: () = assert!(
+                #version == freeswitch_rs::types::SWITCH_API_VERSION as i64,
+                "api_version does not match the SWITCH_API_VERSION of the FreeSWITCH headers",
+            );
+        },
+        None => quote! {},
+    };
 
     let output = quote! {
         // Wrap Load function
@@ -35,21 +139,91 @@ fn impl_switch_module_define(ast: &syn::ItemStruct, mod_name: &syn::Ident) -> To
                 pool: *mut freeswitch_rs::types::switch_memory_pool_t,
             ) -> freeswitch_rs::types::switch_status_t
             {
-                let _ = freeswitch_rs::log::set_logger(&freeswitch_rs::fslog::FSLogger);
-                freeswitch_rs::log::set_max_level(freeswitch_rs::log::LevelFilter::Debug);
+                freeswitch_rs::catch_panic(#load_context, std::ptr::null_mut(), || {
+                    let _ = freeswitch_rs::log::set_logger(&freeswitch_rs::fslog::FSLogger);
+                    freeswitch_rs::log::set_max_level(freeswitch_rs::log::LevelFilter::#log_level);
 
-                let ptr = freeswitch_rs::FSModuleInterface::create(#mod_name_string, pool);
-                if ptr.is_null() { panic!("Module Creation Failed") }
-                *module_interface = *(&ptr);
+                    let ptr = freeswitch_rs::FSModuleInterface::create(#mod_name_string, pool);
+                    if ptr.is_null() {
+                        return freeswitch_rs::types::switch_status_t::SWITCH_STATUS_MEMERR;
+                    }
+                    *module_interface = *(&ptr);
 
-                let pool = freeswitch_rs::FSModulePool(pool);
-                let module = freeswitch_rs::FSModuleInterface(module_interface);
-                #struct_name::load(module,pool)
+                    let pool = freeswitch_rs::FSModulePool(pool);
+                    let module = freeswitch_rs::FSModuleInterface(module_interface);
+                    if let Err(e) = freeswitch_rs::event::Event::reserve_subclasses(#struct_name::EVENT_SUBCLASSES) {
+                        return e.into();
+                    }
+                    #( module.add_api(#apis); )*
+                    #( module.add_application(#apps); )*
+                    #struct_name::shutdown_signal().set(false);
+                    match <#struct_name as freeswitch_rs::LoadableModule>::load(module, pool) {
+                        Ok(instance) => {
+                            let mut guard = <#struct_name as freeswitch_rs::ModuleInstance>::instance()
+                                .write()
+                                .unwrap_or_else(std::sync::PoisonError::into_inner);
+                            *guard = Some(instance);
+                            freeswitch_rs::types::switch_status_t::SWITCH_STATUS_SUCCESS
+                        }
+                        Err(e) => {
+                            freeswitch_rs::FSModuleInterface::remove_api_completions();
+                            freeswitch_rs::event::Event::free_subclasses(#struct_name::EVENT_SUBCLASSES);
+                            e.into()
+                        }
+                    }
+                })
+                .unwrap_or_else(|| {
+                    freeswitch_rs::FSModuleInterface::remove_api_completions();
+                    freeswitch_rs::event::Event::free_subclasses(#struct_name::EVENT_SUBCLASSES);
+                    freeswitch_rs::types::switch_status_t::SWITCH_STATUS_FALSE
+                })
             }
 
             unsafe extern "C" fn shutdown_wrapper() -> freeswitch_rs::types::switch_status_t
             {
-                #struct_name::shutdown()
+                // Raise the signal first, so the runtime returns and releases the instance
+                #struct_name::shutdown_signal().set(true);
+                let instance = <#struct_name as freeswitch_rs::ModuleInstance>::instance()
+                    .write()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner)
+                    .take();
+                let res = freeswitch_rs::catch_panic(#shutdown_context, std::ptr::null_mut(), || {
+                    instance.map(freeswitch_rs::LoadableModule::shutdown)
+                });
+                freeswitch_rs::FSModuleInterface::remove_api_completions();
+                freeswitch_rs::event::Event::free_subclasses(#struct_name::EVENT_SUBCLASSES);
+                match res {
+                    Some(Some(Err(e))) => e.into(),
+                    None => freeswitch_rs::types::switch_status_t::SWITCH_STATUS_FALSE,
+                    _ => freeswitch_rs::types::switch_status_t::SWITCH_STATUS_SUCCESS,
+                }
+            }
+
+            unsafe extern "C" fn runtime_wrapper() -> freeswitch_rs::types::switch_status_t
+            {
+                let signal = #struct_name::shutdown_signal();
+                if signal.is_shutdown() {
+                    return freeswitch_rs::types::switch_status_t::SWITCH_STATUS_TERM;
+                }
+                freeswitch_rs::catch_panic(#runtime_context, std::ptr::null_mut(), || {
+                    <#struct_name as freeswitch_rs::ModuleInstance>::with_module(|m| m.runtime(signal))
+                })
+                .flatten()
+                .unwrap_or(freeswitch_rs::types::switch_status_t::SWITCH_STATUS_TERM)
+            }
+
+            const EVENT_SUBCLASSES: &[&std::ffi::CStr] = &[#(#events),*];
+
+            fn shutdown_signal() -> &'static freeswitch_rs::ShutdownSignal {
+                static SIGNAL: freeswitch_rs::ShutdownSignal = freeswitch_rs::ShutdownSignal::new();
+                &SIGNAL
+            }
+        }
+
+        impl freeswitch_rs::ModuleInstance for #struct_name {
+            fn instance() -> &'static std::sync::RwLock<Option<Self>> {
+                static INSTANCE: std::sync::RwLock<Option<#struct_name>> = std::sync::RwLock::new(None);
+                &INSTANCE
             }
         }
 
@@ -57,21 +231,28 @@ fn impl_switch_module_define(ast: &syn::ItemStruct, mod_name: &syn::Ident) -> To
         #[no_mangle]
         #[allow(non_upper_case_globals)]
         pub static mut #mod_interface_ident: freeswitch_rs::types::switch_loadable_module_function_table = freeswitch_rs::types::switch_loadable_module_function_table {
-            switch_api_version: 5,
+            switch_api_version: freeswitch_rs::types::SWITCH_API_VERSION as ::std::os::raw::c_int,
             load: Some(#struct_name::load_wrapper),
             shutdown: Some(#struct_name::shutdown_wrapper),
-            runtime: None,
-            flags: 0,
+            // The default runtime returns TERM, so modules that don't override it end the thread straight away
+            runtime: Some(#struct_name::runtime_wrapper),
+            flags: #flags,
         };
+
+        #api_version
     };
     //eprintln!("TOKENS: {}", output);
-    TokenStream::from(output)
+    Ok(TokenStream::from(output))
 }
 
 #[derive(Default)]
 struct ApiAttributes {
     name: Option<LitStr>,
     desc: Option<LitStr>,
+    syntax: Option<LitStr>,
+    complete: Vec<LitStr>,
+    args: Option<syn::Type>,
+    format: Option<LitStr>,
 }
 
 impl ApiAttributes {
@@ -82,27 +263,120 @@ impl ApiAttributes {
         } else if meta.path.is_ident("desc") {
             self.desc = Some(meta.value()?.parse()?);
             Ok(())
+        } else if meta.path.is_ident("syntax") {
+            self.syntax = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("complete") {
+            let complete = parse_list(&meta)?
+                .into_iter()
+                .map(|e| match e {
+                    syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Str(ls),
+                        ..
+                    }) => Ok(ls),
+                    e => Err(syn::Error::new_spanned(e, "expected a string literal")),
+                })
+                .collect::<syn::Result<_>>()?;
+            self.complete = complete;
+            Ok(())
+        } else if meta.path.is_ident("args") {
+            self.args = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("format") {
+            self.format = Some(meta.value()?.parse()?);
+            Ok(())
         } else {
             Err(meta.error("unsupported property"))
         }
     }
 }
 
+/// Register a function as an api command, ie `#[switch_api_define(name = "..", desc = "..")]`.
+///
+/// - `syntax`: usage shown by `show api`
+/// - `complete`: fs_cli tab completions for the arguments, ie `["start ::console::list_uuid"]`,
+///   registered on load and removed on shutdown
+/// - `args`: a `clap::Subcommand` the command is parsed into, passed to the handler in place of
+///   `cmd`. Usage and parse errors are written to the stream without calling the handler.
+///
+/// Handlers either return a `switch_status_t`, writing their own output, or a
+/// `Result<impl Display, impl Error>` which is written as `+OK <value>` or `-ERR <error chain>`.
+/// With `format = "json"` the `Result` holds an `impl Serialize`, and is written as
+/// `{"status": "ok", "data": ..}` or `{"status": "error", "message": ".."}`.
 #[proc_macro_attribute]
 pub fn switch_api_define(attr: TokenStream, item: TokenStream) -> TokenStream {
     let ast = syn::parse_macro_input!(item as syn::ItemFn);
     let mut attrs = ApiAttributes::default();
     let parser = syn::meta::parser(|meta| attrs.parse(meta));
     parse_macro_input!(attr with parser);
-    impl_switch_api_define(&ast, attrs)
+    impl_switch_api_define(&ast, attrs).unwrap_or_else(|e| e.to_compile_error().into())
 }
 
-fn impl_switch_api_define(ast: &syn::ItemFn, attrs: ApiAttributes) -> TokenStream {
-    let syn::ItemFn { sig, block, .. } = ast;
+fn impl_switch_api_define(ast: &syn::ItemFn, attrs: ApiAttributes) -> syn::Result<TokenStream> {
+    let syn::ItemFn { sig, .. } = ast;
+    let json = match &attrs.format {
+        Some(format) if format.value() == "json" => true,
+        Some(format) if format.value() == "text" => false,
+        Some(format) => {
+            return Err(syn::Error::new_spanned(
+                format,
+                "expected a format of \"text\" or \"json\"",
+            ))
+        }
+        None => false,
+    };
+    let returns = match json {
+        true => "Result<impl Serialize, impl Error>",
+        false => "switch_status_t",
+    };
+    let expected = match &attrs.args {
+        Some(args) => format!(
+            "(args: {}, session: Option<&Session>, stream: StreamHandle) -> {returns}",
+            quote! { #args }
+        ),
+        None => {
+            format!("(cmd: &str, session: Option<&Session>, stream: StreamHandle) -> {returns}")
+        }
+    };
+    let module = validate_handler(sig, &expected, 3, true)?;
 
     let name = &sig.ident;
     let fs_name = attrs.name.map(|ls| ls.value()).unwrap_or(name.to_string());
     let fs_desc = attrs.desc.map(|ls| ls.value()).unwrap_or("".to_string());
+    let fs_syntax = attrs.syntax.map(|ls| ls.value()).unwrap_or("".to_string());
+    let complete = attrs.complete;
+    // Spanned so unsupported return types point at the handler's return type
+    let respond = match (&sig.output, json) {
+        (syn::ReturnType::Type(_, ty), true) => quote_spanned! {ty.span()=>
+            freeswitch_rs::api::JsonApiResponse::write_json_response
+        },
+        (syn::ReturnType::Type(_, ty), false) => quote_spanned! {ty.span()=>
+            freeswitch_rs::api::ApiResponse::write_response
+        },
+        _ => unreachable!("validate_handler requires a return type"),
+    };
+    // Spanned so type errors point at the handler's signature
+    let call = match module {
+        Some(module) => quote_spanned! {sig.ident.span()=>
+            <#module as freeswitch_rs::ModuleInstance>::with_module(|m| #respond(#name::#name(m,cmd,session,stream), &mut freeswitch_rs::StreamHandle(raw)))
+                .unwrap_or(freeswitch_rs::types::switch_status_t::SWITCH_STATUS_FALSE)
+        },
+        None => quote_spanned! {sig.ident.span()=>
+            #respond(#name::#name(cmd,session,stream), &mut freeswitch_rs::StreamHandle(raw))
+        },
+    };
+    // The handler takes the stream, so the response is written through a copy of the ptr
+    let call = quote! {{
+        let raw = stream.0;
+        #call
+    }};
+    // The parsed args take the place of the cmd str
+    let call = match &attrs.args {
+        Some(args) => quote! {
+            freeswitch_rs::api::call_with_args::<#args, _>(#fs_name, cmd, stream, |cmd, stream| #call)
+        },
+        None => call,
+    };
 
     let output = quote! {
         #[allow(non_camel_case_types)]
@@ -114,49 +388,317 @@ fn impl_switch_api_define(ast: &syn::ItemFn, attrs: ApiAttributes) -> TokenStrea
         impl freeswitch_rs::ApiInterface for #name {
             const NAME:&'static str = #fs_name;
             const DESC:&'static str = #fs_desc;
+            const SYNTAX:&'static str = #fs_syntax;
+            const COMPLETE:&'static [&'static str] = &[#(#complete),*];
             fn api_fn(cmd:&str, session:Option<&freeswitch_rs::core::Session>, stream:freeswitch_rs::StreamHandle) -> freeswitch_rs::types::switch_status_t {
-                #name::#name(cmd,session,stream)
+                #call
             }
             unsafe extern "C" fn api_fn_raw(
                 cmd: *const ::std::os::raw::c_char,
                 session: *mut freeswitch_rs::types::switch_core_session_t,
                 stream: *mut freeswitch_rs::types::switch_stream_handle_t,
             ) -> freeswitch_rs::types::switch_status_t {
-                let cstr = std::ffi::CStr::from_ptr(cmd);
-                let session = None;
-                let stream = freeswitch_rs::StreamHandle(stream);
-                #name::api_fn(cstr.to_str().unwrap(),session,stream)
+                use freeswitch_rs::prelude::FSNewType;
+                freeswitch_rs::catch_panic(#fs_name, session, || {
+                    let cmd = if cmd.is_null() {
+                        std::borrow::Cow::Borrowed("")
+                    } else {
+                        std::ffi::CStr::from_ptr(cmd).to_string_lossy()
+                    };
+                    // The wrapper only lives for the duration of the call,
+                    // so handlers can't hold onto the borrowed session
+                    let session = (!session.is_null()).then(|| freeswitch_rs::core::Session::from_ptr(session));
+                    let stream = freeswitch_rs::StreamHandle(stream);
+                    #name::api_fn(&cmd,session.as_ref(),stream)
+                })
+                .unwrap_or_else(|| {
+                    freeswitch_rs::api::write_panic_response(#fs_name, &mut freeswitch_rs::StreamHandle(stream))
+                })
             }
         }
     };
 
     //eprintln!("TOKENS1: {}", sig.ident);
     //eprintln!("TOKENS: {}", output);
-    TokenStream::from(output)
+    Ok(TokenStream::from(output))
+}
+
+#[proc_macro_attribute]
+pub fn switch_json_api_define(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let ast = syn::parse_macro_input!(item as syn::ItemFn);
+    let mut attrs = ApiAttributes::default();
+    let parser = syn::meta::parser(|meta| attrs.parse(meta));
+    parse_macro_input!(attr with parser);
+    impl_switch_json_api_define(&ast, attrs).unwrap_or_else(|e| e.to_compile_error().into())
+}
+
+fn impl_switch_json_api_define(
+    ast: &syn::ItemFn,
+    attrs: ApiAttributes,
+) -> syn::Result<TokenStream> {
+    let syn::ItemFn { sig, .. } = ast;
+    let module = validate_handler(
+        sig,
+        "(request: impl Deserialize, session: Option<&Session>) -> Result<impl Serialize, impl Display>",
+        2,
+        true,
+    )?;
+    if module.is_some() {
+        return Err(syn::Error::new_spanned(
+            &sig.inputs,
+            "json apis only take the request and session",
+        ));
+    }
+    if let Some(syntax) = &attrs.syntax {
+        return Err(syn::Error::new_spanned(syntax, "json apis have no syntax"));
+    }
+    if let Some(args) = &attrs.args {
+        return Err(syn::Error::new_spanned(
+            args,
+            "json apis take their request from the json `data`",
+        ));
+    }
+    if let Some(format) = &attrs.format {
+        return Err(syn::Error::new_spanned(
+            format,
+            "json apis always reply in json",
+        ));
+    }
+    if let Some(complete) = attrs.complete.first() {
+        return Err(syn::Error::new_spanned(
+            complete,
+            "json apis have no console completions",
+        ));
+    }
+
+    let name = &sig.ident;
+    let fs_name = attrs.name.map(|ls| ls.value()).unwrap_or(name.to_string());
+    let fs_desc = attrs.desc.map(|ls| ls.value()).unwrap_or("".to_string());
+    let call = quote_spanned! {sig.ident.span()=> #name::#name };
+
+    let output = quote! {
+        #[allow(non_camel_case_types)]
+        struct #name;
+        impl #name {
+            #ast
+        }
+
+        impl freeswitch_rs::json::JsonApiInterface for #name {
+            const NAME:&'static str = #fs_name;
+            const DESC:&'static str = #fs_desc;
+            unsafe extern "C" fn json_fn_raw(
+                json: *const freeswitch_rs::types::cJSON,
+                session: *mut freeswitch_rs::types::switch_core_session_t,
+                json_reply: *mut *mut freeswitch_rs::types::cJSON,
+            ) -> freeswitch_rs::types::switch_status_t {
+                freeswitch_rs::catch_panic(#fs_name, session, || {
+                    freeswitch_rs::json::call_json_api(json, session, json_reply, #call)
+                })
+                .unwrap_or(freeswitch_rs::types::switch_status_t::SWITCH_STATUS_FALSE)
+            }
+        }
+    };
+    Ok(TokenStream::from(output))
+}
+
+#[derive(Default)]
+struct AppAttributes {
+    name: Option<LitStr>,
+    desc: Option<LitStr>,
+    syntax: Option<LitStr>,
+    flags: Option<syn::Expr>,
+}
+
+impl AppAttributes {
+    fn parse(&mut self, meta: ParseNestedMeta) -> syn::parse::Result<()> {
+        if meta.path.is_ident("name") {
+            self.name = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("desc") {
+            self.desc = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("syntax") {
+            self.syntax = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("flags") {
+            self.flags = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("unsupported property"))
+        }
+    }
 }
 
 #[proc_macro_attribute]
-pub fn switch_state_handler(_: TokenStream, item: TokenStream) -> TokenStream {
+pub fn switch_app_define(attr: TokenStream, item: TokenStream) -> TokenStream {
     let ast = syn::parse_macro_input!(item as syn::ItemFn);
-    impl_switch_state_handler(&ast)
+    let mut attrs = AppAttributes::default();
+    let parser = syn::meta::parser(|meta| attrs.parse(meta));
+    parse_macro_input!(attr with parser);
+    impl_switch_app_define(&ast, attrs).unwrap_or_else(|e| e.to_compile_error().into())
 }
 
-fn impl_switch_state_handler(ast: &syn::ItemFn) -> TokenStream {
+fn impl_switch_app_define(ast: &syn::ItemFn, attrs: AppAttributes) -> syn::Result<TokenStream> {
     let syn::ItemFn { sig, .. } = ast;
+    let module = validate_handler(sig, "(session: &Session, data: &str)", 2, false)?;
+
     let name = &sig.ident;
+    let fs_name = attrs.name.map(|ls| ls.value()).unwrap_or(name.to_string());
+    let fs_desc = attrs.desc.map(|ls| ls.value()).unwrap_or("".to_string());
+    let fs_syntax = attrs.syntax.map(|ls| ls.value()).unwrap_or("".to_string());
+    let call = match module {
+        Some(module) => quote_spanned! {sig.ident.span()=>
+            <#module as freeswitch_rs::ModuleInstance>::with_module(|m| #name::#name(m,session,data));
+        },
+        None => quote_spanned! {sig.ident.span()=> #name::#name(session,data) },
+    };
+    let flags = attrs.flags.map(|flags| {
+        quote! {
+            fn flags() -> freeswitch_rs::ApplicationFlags {
+                #flags
+            }
+        }
+    });
+
     let output = quote! {
-        mod #name {
-            use freeswitch_rs::core::Session;
-            use freeswitch_sys::types::switch_status_t;
-            use crate::*;
-            use super::*;
+        #[allow(non_camel_case_types)]
+        struct #name;
+        impl #name {
             #ast
         }
 
-        unsafe extern "C" fn #name(session: *mut freeswitch_rs::types::switch_core_session_t) -> freeswitch_rs::switch_status_t{
-            let s= freeswitch_rs::Session(session);
-            #name::#name(&s)
+        impl freeswitch_rs::ApplicationInterface for #name {
+            const NAME:&'static str = #fs_name;
+            const DESC:&'static str = #fs_desc;
+            const SYNTAX:&'static str = #fs_syntax;
+            #flags
+            fn app_fn(session:&freeswitch_rs::core::Session, data:&str) {
+                #call
+            }
+            unsafe extern "C" fn app_fn_raw(
+                session: *mut freeswitch_rs::types::switch_core_session_t,
+                data: *const ::std::os::raw::c_char,
+            ) {
+                use freeswitch_rs::prelude::FSNewType;
+                if session.is_null() {
+                    return;
+                }
+                freeswitch_rs::catch_panic(#fs_name, session, || {
+                    let data = if data.is_null() {
+                        std::borrow::Cow::Borrowed("")
+                    } else {
+                        std::ffi::CStr::from_ptr(data).to_string_lossy()
+                    };
+                    let session = freeswitch_rs::core::Session::from_ptr(session);
+                    #name::app_fn(&session,&data)
+                });
+            }
+        }
+    };
+    Ok(TokenStream::from(output))
+}
+
+// Check a handler's shape, so mistakes are reported against the handler rather than the
+// generated wrapper. Handlers may take the loaded module as a leading `&Module` argument,
+// ahead of the `args` they'd otherwise expect, which is returned if present.
+fn validate_handler<'a>(
+    sig: &'a syn::Signature,
+    expected: &str,
+    args: usize,
+    returns: bool,
+) -> syn::Result<Option<&'a syn::Type>> {
+    if let Some(asyncness) = &sig.asyncness {
+        return Err(syn::Error::new_spanned(
+            asyncness,
+            "handlers are called synchronously from FreeSWITCH, so can't be async",
+        ));
+    }
+    if !sig.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &sig.generics,
+            "handlers can't be generic",
+        ));
+    }
+    if let Some(receiver) = sig.receiver() {
+        return Err(syn::Error::new_spanned(
+            receiver,
+            "handlers must be free functions",
+        ));
+    }
+    if sig.inputs.len() != args && sig.inputs.len() != args + 1 {
+        return Err(syn::Error::new(
+            sig.paren_token.span.join(),
+            format!("expected a handler of the form `fn {}{expected}`, optionally taking `&Module` first", sig.ident),
+        ));
+    }
+    match (&sig.output, returns) {
+        (syn::ReturnType::Default, true) => {
+            return Err(syn::Error::new(
+                sig.paren_token.span.close(),
+                format!("expected a return type, `fn {}{expected}`", sig.ident),
+            ))
+        }
+        (syn::ReturnType::Type(arrow, ty), false) => {
+            return Err(syn::Error::new_spanned(
+                quote! { #arrow #ty },
+                "handler can't return a value",
+            ))
+        }
+        _ => {}
+    }
+    if sig.inputs.len() == args {
+        return Ok(None);
+    }
+    match sig.inputs.first() {
+        Some(syn::FnArg::Typed(pat)) => match pat.ty.as_ref() {
+            syn::Type::Reference(r) if r.mutability.is_none() => Ok(Some(r.elem.as_ref())),
+            ty => Err(syn::Error::new_spanned(
+                ty,
+                "the module must be borrowed, ie `&Module`",
+            )),
+        },
+        _ => Ok(None),
+    }
+}
+
+/// Wrap `fn(&Session) -> switch_status_t` as a state handler for a `switch_state_handler_table_t`.
+#[proc_macro_attribute]
+pub fn switch_state_handler(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let ast = syn::parse_macro_input!(item as syn::ItemFn);
+    parse_macro_input!(attr as syn::parse::Nothing);
+    impl_switch_state_handler(&ast).unwrap_or_else(|e| e.to_compile_error().into())
+}
+
+fn impl_switch_state_handler(ast: &syn::ItemFn) -> syn::Result<TokenStream> {
+    validate_handler(&ast.sig, "(session: &Session) -> switch_status_t", 1, true)?;
+    if ast.sig.inputs.len() != 1 {
+        return Err(syn::Error::new_spanned(
+            &ast.sig.inputs,
+            "state handlers only take the session",
+        ));
+    }
+    let name = &ast.sig.ident;
+    let vis = &ast.vis;
+    // The user's fn is nested inside the C callback of the same name, shadowing it
+    let mut inner = ast.clone();
+    inner.vis = syn::Visibility::Inherited;
+    let call = quote_spanned! {name.span()=> #name(&s) };
+    let context = name.to_string();
+    let output = quote! {
+        #vis unsafe extern "C" fn #name(
+            session: *mut freeswitch_rs::types::switch_core_session_t,
+        ) -> freeswitch_rs::types::switch_status_t {
+            use freeswitch_rs::prelude::FSNewType;
+            #inner
+            if session.is_null() {
+                return freeswitch_rs::types::switch_status_t::SWITCH_STATUS_FALSE;
+            }
+            freeswitch_rs::catch_panic(#context, session, || {
+                let s = freeswitch_rs::core::Session::from_ptr(session);
+                #call
+            })
+            .unwrap_or(freeswitch_rs::types::switch_status_t::SWITCH_STATUS_FALSE)
         }
     };
-    TokenStream::from(output)
+    Ok(TokenStream::from(output))
 }
@@ -0,0 +1,8 @@
+use freeswitch_rs::prelude::*;
+
+#[switch_api_define]
+fn test_api(_cmd: &str) -> switch_status_t {
+    switch_status_t::SWITCH_STATUS_SUCCESS
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+#![allow(unused_imports)]
+
+use freeswitch_rs::prelude::*;
+use freeswitch_rs::core::Session;
+
+#[switch_api_define]
+fn test_api(_cmd: &str, _session: Option<&Session>, _stream: StreamHandle) {}
+
+fn main() {}
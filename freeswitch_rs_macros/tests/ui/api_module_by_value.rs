@@ -0,0 +1,18 @@
+#![allow(unused_imports)]
+
+use freeswitch_rs::prelude::*;
+use freeswitch_rs::core::Session;
+
+struct FSModule;
+
+#[switch_api_define]
+fn test_api(
+    _module: FSModule,
+    _cmd: &str,
+    _session: Option<&Session>,
+    _stream: StreamHandle,
+) -> switch_status_t {
+    switch_status_t::SWITCH_STATUS_SUCCESS
+}
+
+fn main() {}
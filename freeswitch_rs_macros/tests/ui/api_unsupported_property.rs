@@ -0,0 +1,11 @@
+#![allow(unused_imports)]
+
+use freeswitch_rs::prelude::*;
+use freeswitch_rs::core::Session;
+
+#[switch_api_define(name = "test", description = "a test api")]
+fn test_api(_cmd: &str, _session: Option<&Session>, _stream: StreamHandle) -> switch_status_t {
+    switch_status_t::SWITCH_STATUS_SUCCESS
+}
+
+fn main() {}
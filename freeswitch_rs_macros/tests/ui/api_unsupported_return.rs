@@ -0,0 +1,9 @@
+use freeswitch_rs::prelude::*;
+use freeswitch_rs::core::Session;
+
+#[switch_api_define]
+fn api(_cmd: &str, _session: Option<&Session>, _stream: StreamHandle) -> i32 {
+    0
+}
+
+fn main() {}
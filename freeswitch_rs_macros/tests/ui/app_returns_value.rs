@@ -0,0 +1,11 @@
+#![allow(unused_imports)]
+
+use freeswitch_rs::prelude::*;
+use freeswitch_rs::core::Session;
+
+#[switch_app_define(name = "test")]
+fn test_app(_session: &Session, _data: &str) -> switch_status_t {
+    switch_status_t::SWITCH_STATUS_SUCCESS
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use freeswitch_rs::prelude::*;
+
+#[switch_module_define(mod_test)]
+struct FSModule<T> {
+    state: T,
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+use freeswitch_rs::prelude::*;
+
+#[switch_module_define(mod_test, log_level = "verbose")]
+struct FSModule;
+
+fn main() {}
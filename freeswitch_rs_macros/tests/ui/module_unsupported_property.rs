@@ -0,0 +1,6 @@
+use freeswitch_rs::prelude::*;
+
+#[switch_module_define(mod_test, flag = ModuleFlags::SMODF_GLOBAL_SYMBOLS)]
+struct FSModule;
+
+fn main() {}
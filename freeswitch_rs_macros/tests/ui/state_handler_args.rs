@@ -0,0 +1,12 @@
+#![allow(unused_imports)]
+
+use freeswitch_rs::channel::switch_state_handler;
+use freeswitch_rs::core::Session;
+use freeswitch_rs::types::switch_status_t;
+
+#[switch_state_handler]
+fn on_destroy(_session: &Session, _extra: u32) -> switch_status_t {
+    switch_status_t::SWITCH_STATUS_SUCCESS
+}
+
+fn main() {}
@@ -22,6 +22,12 @@ fn main() {
         .newtype_enum("^switch_status_t")
         .allowlist_item("switch_media_bug_flag.*")
         .bitfield_enum("switch_media_bug_flag.*")
+        // Strings handed back to FS are released with free()
+        .allowlist_function("^strdup$")
+        .allowlist_function("^free$")
+        .allowlist_function("^malloc$")
+        // Json
+        .allowlist_item("^cJSON.*")
         // Session
         .allowlist_item("^switch_core_session.*")
         .allowlist_item("^switch_core_perform_session.*")
@@ -34,12 +40,35 @@ fn main() {
         .allowlist_item("^switch_loadable_module_function_table_t")
         .allowlist_item("^switch_loadable_module_create_interface")
         .allowlist_item("^switch_loadable_module_create_module_interface")
+        .allowlist_function("^switch_console_set_complete$")
+        // Api
+        .allowlist_function("^switch_api_execute$")
+        .allowlist_function("^switch_console_stream_(raw_)?write$")
         .allowlist_item("^switch_.*?_interface_t")
+        .bitfield_enum("switch_application_flag_enum_t")
+        .bitfield_enum("switch_module_flag_enum_t")
         .newtype_enum("^switch_abc_type_t")
         .newtype_enum("^switch_module_interface_name_t")
         // Events
         .allowlist_item("^switch_event.*")
+        .allowlist_item("^switch_core_chat.*")
         .newtype_enum("^switch_event_types_t")
+        // Endpoints
+        .allowlist_item("^switch_caller.*")
+        .newtype_enum("^switch_call_cause_t")
+        .newtype_enum("^switch_signal_t")
+        .newtype_enum("^switch_core_session_message_types_t")
+        // Codecs
+        .allowlist_item("^switch_core_codec.*")
+        .bitfield_enum("switch_codec_flag_enum_t")
+        // Say
+        .allowlist_item("^switch_ivr_play_file")
+        .newtype_enum("^switch_say_type_t")
+        .newtype_enum("^switch_say_method_t")
+        .newtype_enum("^switch_say_gender_t")
+        // Xml
+        .allowlist_item("^switch_xml.*")
+        .bitfield_enum("switch_xml_section_enum_t")
         // Channels
         .allowlist_item("^switch_state_handler.*")
         .allowlist_item("^switch_channel.*")
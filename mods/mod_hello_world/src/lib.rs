@@ -1,15 +1,14 @@
-use freeswitch_rs::core::Session;
+use freeswitch_rs::core::{Session, SessionExt};
 use freeswitch_rs::log::{debug, info};
 use freeswitch_rs::prelude::*;
 
-#[switch_module_define(mod_hello_world)]
+#[switch_module_define(mod_hello_world, apis = [hello_world], apps = [hello_world_app])]
 struct FSModule;
 
 impl LoadableModule for FSModule {
-    fn load(module: FSModuleInterface, _pool: FSModulePool) -> switch_status_t {
+    fn load(_module: FSModuleInterface, _pool: FSModulePool) -> Result<Self> {
         info!("mod hello_world loading");
-        module.add_api(hello_world);
-        switch_status_t::SWITCH_STATUS_SUCCESS
+        Ok(FSModule)
     }
 }
 // api will use function name if no 'name' attribute provided to proc macro
@@ -19,3 +18,14 @@ fn hello_world(cmd: &str, _session: Option<&Session>, mut stream: StreamHandle)
     let _ = writeln!(stream, "+OK Success");
     switch_status_t::SWITCH_STATUS_SUCCESS
 }
+
+#[switch_app_define(
+    name = "hello_world",
+    desc = "log a greeting for the channel",
+    syntax = "<greeting>",
+    flags = ApplicationFlags::SAF_SUPPORT_NOMEDIA
+)]
+fn hello_world_app(session: &Session, data: &str) {
+    let uuid = session.get_uuid().to_string_lossy();
+    info!(logger:session_log!(session), "hello world {} {}", uuid, data);
+}
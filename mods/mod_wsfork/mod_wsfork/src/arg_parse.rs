@@ -1,7 +1,7 @@
 use std::{ffi::CString, net::SocketAddr, str::FromStr};
 
 use anyhow::{Result, anyhow};
-use clap::{Command, FromArgMatches as _, Parser, Subcommand as _, ValueEnum};
+use clap::{Parser, Subcommand, ValueEnum};
 use http_body_util::Empty;
 use hyper::{
     Method, Request,
@@ -30,6 +30,7 @@ pub(crate) enum AudioMix {
 #[derive(Parser, Debug, Clone)]
 pub(crate) struct Endpoint {
     pub url: Url,
+    /// JSON object of headers, single-quoted so its quotes survive, ie '{"X-Token":"abc"}'
     pub headers: String,
 }
 
@@ -79,7 +80,7 @@ impl Endpoint {
     }
 }
 
-#[derive(Parser, Debug)]
+#[derive(Subcommand, Debug)]
 pub(crate) enum Subcommands {
     Start {
         #[command(flatten)]
@@ -120,15 +121,3 @@ impl Subcommands {
         }
     }
 }
-
-pub(crate) fn parse_args(cmd_str: &str) -> Result<Subcommands> {
-    let mut cmd = Command::new("wsfork")
-        .disable_version_flag(true)
-        .disable_help_flag(true)
-        .ignore_errors(true)
-        .no_binary_name(true);
-    cmd = Subcommands::augment_subcommands(cmd);
-    let matches = cmd.try_get_matches_from(cmd_str.split(' '))?;
-    let s = Subcommands::from_arg_matches(&matches)?;
-    Ok(s)
-}
@@ -2,7 +2,7 @@ mod arg_parse;
 mod audio_fork;
 mod mod_wsfork_api;
 
-use crate::arg_parse::{Common, Subcommands, parse_args};
+use crate::arg_parse::{Common, Subcommands};
 use anyhow::anyhow;
 use freeswitch_rs::prelude::*;
 use freeswitch_rs::{
@@ -10,60 +10,79 @@ use freeswitch_rs::{
     event::Event,
     log::*,
 };
-use std::ffi::CStr;
+use std::ffi::{CStr, CString};
 use std::ops::Deref;
-use std::sync::OnceLock;
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
 use tokio::runtime::{Builder, Runtime};
 use wsfork_events::{Body, WSForkEvent};
 
 pub use wsfork_events::MOD_WSFORK_EVENT;
 
-static RT: OnceLock<Runtime> = OnceLock::new();
-
-#[switch_module_define(mod_wsfork)]
-struct FSMod;
+#[switch_module_define(mod_wsfork, apis = [api_main], events = [MOD_WSFORK_EVENT])]
+struct FSMod {
+    runtime: Runtime,
+    // (session, fork) of every fork started, removed on shutdown if still attached
+    forks: Mutex<Vec<(CString, CString)>>,
+}
 
 impl LoadableModule for FSMod {
-    fn load(module: FSModuleInterface, _pool: FSModulePool) -> switch_status_t {
+    fn load(_module: FSModuleInterface, _pool: FSModulePool) -> Result<Self> {
         info!("mod ws_fork loading");
         // TODO: make worker count configurable
-        let Ok(runtime) = Builder::new_multi_thread()
+        let runtime = Builder::new_multi_thread()
             .enable_all()
             .worker_threads(5)
             .build()
-        else {
-            return switch_status_t::SWITCH_STATUS_FALSE;
-        };
-        let _ = RT.set(runtime);
-
-        module.add_api(api_main);
-
-        if Event::reserve_subclass(MOD_WSFORK_EVENT).is_err() {
-            error!("Failure to register custom events");
-            return switch_status_t::SWITCH_STATUS_FALSE;
-        }
+            .map_err(|_| switch_status_t::SWITCH_STATUS_FALSE)?;
 
-        switch_status_t::SWITCH_STATUS_SUCCESS
+        Ok(FSMod {
+            runtime,
+            forks: Mutex::new(vec![]),
+        })
     }
 
-    fn shutdown() -> switch_status_t {
+    fn shutdown(self) -> Result<()> {
         info!("mod ws_fork shutdown");
-        let _ = Event::free_subclass(MOD_WSFORK_EVENT);
-        switch_status_t::SWITCH_STATUS_SUCCESS
+        // Attached bugs block on the runtime as they close, so remove them while it's still running
+        let forks = self
+            .forks
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+        for (session_id, fork_id) in forks {
+            if let Some(session) = Session::locate(&session_id)
+                && let Err(e) = mod_wsfork_api::api_remove(&session, &fork_id)
+            {
+                error!(logger:session_log!(session.deref()), "mod wsfork error: {}", e);
+            }
+        }
+        // Any delayed cleanup still pending is dropped along with the runtime
+        self.runtime.shutdown_timeout(Duration::from_secs(5));
+        Ok(())
     }
 }
 
-#[switch_api_define(name = "wsfork", desc = "fork audio frames over websocket")]
-fn api_main(cmd: &str, _session: Option<&Session>, mut stream: StreamHandle) -> switch_status_t {
-    debug!("mod wsfork cmd {}", &cmd);
-
-    let cmd = match parse_args(cmd) {
-        Err(e) => {
-            error!("mod wsfork invalid usage:\n{}", &e);
-            return switch_status_t::SWITCH_STATUS_SUCCESS;
-        }
-        Ok(cmd) => cmd,
-    };
+#[switch_api_define(
+    name = "wsfork",
+    desc = "fork audio frames over websocket",
+    args = Subcommands,
+    syntax = "start <uuid> <fork_id> <url> '<headers-json>' <mono|stereo|mixed> [start_paused] | \
+              <stop|pause|resume> <uuid> <fork_id> | send-text <uuid> <fork_id> <text>",
+    complete = [
+        "start ::console::list_uuid",
+        "stop ::console::list_uuid",
+        "pause ::console::list_uuid",
+        "resume ::console::list_uuid",
+        "send-text ::console::list_uuid",
+    ]
+)]
+fn api_main(
+    module: &FSMod,
+    cmd: Subcommands,
+    _session: Option<&Session>,
+    _stream: StreamHandle,
+) -> anyhow::Result<&'static str> {
+    debug!("mod wsfork cmd {:?}", &cmd);
 
     let Common {
         session_id,
@@ -71,11 +90,10 @@ fn api_main(cmd: &str, _session: Option<&Session>, mut stream: StreamHandle) ->
     } = cmd.common_args();
 
     let Some(session) = Session::locate(session_id) else {
-        error!(
+        return Err(anyhow!(
             "Failed to find session {}",
             session_id.to_owned().into_string().unwrap_or_default()
-        );
-        return switch_status_t::SWITCH_STATUS_SUCCESS;
+        ));
     };
 
     let res = match &cmd {
@@ -86,16 +104,26 @@ fn api_main(cmd: &str, _session: Option<&Session>, mut stream: StreamHandle) ->
             ..
         } => {
             let s = session_id.to_owned();
-            let runtime = RT.get().expect("async runtime has been initialised");
-            mod_wsfork_api::api_start(
+            let res = mod_wsfork_api::api_start(
                 &session,
                 fork_id,
                 endpoint,
                 *mix,
                 start_paused.unwrap_or(false),
                 move |event| response_handler(&s, event),
-                runtime,
-            )
+                module.runtime.handle().clone(),
+            );
+            if res.is_ok() {
+                let mut forks = module.forks.lock().unwrap_or_else(PoisonError::into_inner);
+                // Forget forks that have since ended
+                forks.retain(|(session_id, fork_id)| {
+                    Session::locate(session_id).is_some_and(|s| {
+                        mod_wsfork_api::PrivateSessionData::get(&s, fork_id).is_some()
+                    })
+                });
+                forks.push((session_id.to_owned(), fork_id.to_owned()));
+            }
+            res
         }
         other_cmds => mod_wsfork_api::PrivateSessionData::get(&session, fork_id)
             .as_deref()
@@ -112,14 +140,10 @@ fn api_main(cmd: &str, _session: Option<&Session>, mut stream: StreamHandle) ->
             ))),
     };
 
-    if let Err(e) = res {
-        error!(logger:session_log!(session.deref()), "mod wsfork error: {}", &e);
-        let _ = write!(stream, "-ERR, mod wsfork operation failed");
-    } else {
-        let _ = write!(stream, "+OK Success");
+    if let Err(e) = &res {
+        error!(logger:session_log!(session.deref()), "mod wsfork error: {}", e);
     }
-
-    switch_status_t::SWITCH_STATUS_SUCCESS
+    res.map(|_| "Success")
 }
 
 fn response_handler(session_id: &CStr, change: Body) {
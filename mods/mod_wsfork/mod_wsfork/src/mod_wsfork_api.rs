@@ -78,6 +78,16 @@ impl PrivateSessionData {
     }
 }
 
+/// Detach a fork's bug straight away, rather than waiting for the media thread to prune it.
+pub(crate) fn api_remove(session: &Session, fork_name: &CStr) -> Result<()> {
+    let Some(bug) = PrivateSessionData::get(session, fork_name)
+        .and_then(|data| data.bug.lock().unwrap().take())
+    else {
+        return Ok(());
+    };
+    Ok(session.remove_media_bug(bug)?)
+}
+
 pub(crate) fn api_start(
     session: &Session,
     fork_name: &CStr,
@@ -85,7 +95,7 @@ pub(crate) fn api_start(
     audio_mix: AudioMix,
     start_paused: bool,
     response_handler: impl Fn(Body) + Send + Sync + 'static + Clone,
-    runtime: &'static runtime::Runtime,
+    runtime: runtime::Handle,
 ) -> Result<()> {
     debug!(logger:session_log!(session), "Getting Read Impl");
     let read_impl = unsafe {